@@ -0,0 +1,121 @@
+//! Compares the anchored SIMD scanner against the previous byte by byte implementation.
+//!
+//! Run with `cargo bench --bench scan`.
+
+use rayon::iter::IndexedParallelIterator;
+use rayon::slice::ParallelSlice;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use transcend::ptr::{scan, scan_all, scan_many, Address, Pattern};
+
+const SIZE: usize = 128 * 1024 * 1024;
+const ITERATIONS: u32 = 10;
+
+/// The implementation `scan` used before the anchored engine.
+fn naive(slice: &[u8], pattern: &Pattern) -> Option<Address> {
+    slice
+        .par_windows(pattern.len())
+        .position_first(|window| pattern.matches(window))
+        .map(|offset| Address::new(slice.as_ptr() as usize + offset))
+}
+
+/// Fills an image with bytes that roughly follow the distribution of x86-64 code, so common bytes like
+/// `48` or `8B` produce plenty of false candidates.
+fn image() -> Vec<u8> {
+    const COMMON: [u8; 16] = [
+        0x00, 0x48, 0x8B, 0x89, 0xFF, 0x0F, 0xE8, 0x24, 0x4C, 0x85, 0x41, 0x44, 0x8D, 0x83, 0xC0,
+        0x74,
+    ];
+
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+
+            match state % 4 {
+                0 => (state >> 8) as u8,
+                _ => COMMON[(state >> 8) as usize % COMMON.len()],
+            }
+        })
+        .collect()
+}
+
+fn measure(name: &str, mut f: impl FnMut()) -> Duration {
+    f();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+
+    println!("{name:<40} {elapsed:>12.2?}");
+    elapsed
+}
+
+fn main() {
+    let mut image = image();
+
+    let signatures = [
+        "48 89 5C 24 ?? 57 48 83 EC ??",
+        "E8 ?? ?? ?? ?? 48 8B 0D ?? ?? ?? ??",
+        "4? 8B ?5 ?? ?? ?? ?? FF 15",
+        "40 53 48 83 EC 20 48 8B D9 E8 ?? ?? ?? ?? 84 C0",
+    ];
+
+    for signature in signatures {
+        let pattern: Pattern = signature.parse().unwrap();
+
+        // Plant the signature close to the end so both implementations walk the whole image.
+        let at = SIZE - 4096;
+        image[at..at + pattern.len()].copy_from_slice(pattern.bytes());
+
+        println!("{signature}");
+
+        let before = measure("  naive", || {
+            black_box(naive(black_box(&image), &pattern));
+        });
+        let after = measure("  scan", || {
+            let _ = black_box(scan(black_box(&image), &pattern));
+        });
+        measure("  scan_all", || {
+            black_box(scan_all(black_box(&image), &pattern));
+        });
+
+        assert_eq!(naive(&image, &pattern), scan(&image, &pattern).ok());
+        println!(
+            "  speedup {:.1}x\n",
+            before.as_secs_f64() / after.as_secs_f64()
+        );
+    }
+
+    // A mod resolving a few hundred signatures at startup, each cut out of the image with a wildcarded
+    // displacement in the middle.
+    let patterns: Vec<Pattern> = (0..200)
+        .map(|index| {
+            let at = (index * 7919 * 4099) % (SIZE - 16);
+            let mask = [
+                0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ];
+            Pattern::new(&image[at..at + mask.len()], mask).unwrap()
+        })
+        .collect();
+
+    println!("{} signatures", patterns.len());
+
+    let before = measure("  scan each", || {
+        for pattern in &patterns {
+            let _ = black_box(scan(black_box(&image), pattern));
+        }
+    });
+    let after = measure("  scan_many", || {
+        black_box(scan_many(black_box(&image), &patterns));
+    });
+
+    println!(
+        "  speedup {:.1}x",
+        before.as_secs_f64() / after.as_secs_f64()
+    );
+}
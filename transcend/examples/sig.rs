@@ -0,0 +1,26 @@
+use transcend::ptr::{sig, Pattern};
+
+fn main() {
+    let sig = sig![55 48 89 E5 66 B8 ?? ?? 48 8B 5D FF];
+
+    assert_eq!(
+        &[0x55, 0x48, 0x89, 0xE5, 0x66, 0xB8, 0x00, 0x00, 0x48, 0x8B, 0x5D, 0xFF],
+        sig.bytes()
+    );
+    assert_eq!(
+        &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF],
+        sig.mask()
+    );
+
+    let sig = sig![4? 8B ?5 ??];
+
+    assert_eq!(&[0x40, 0x8B, 0x05, 0x00], sig.bytes());
+    assert_eq!(&[0xF0, 0xFF, 0x0F, 0x00], sig.mask());
+    assert_eq!(Ok(sig), "4? 8B ?5 ??".parse::<Pattern>());
+
+    let sig = sig![48 8D 0D &?? ?? ?? ?? E8];
+
+    assert_eq!(3, sig.offset());
+    assert_eq!("48 8D 0D &?? ?? ?? ?? E8", sig.to_string());
+    assert_eq!(Ok(sig), "48 8D 0D & ? ? ? ? E8".parse::<Pattern>());
+}
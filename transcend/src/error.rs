@@ -0,0 +1,166 @@
+use core::fmt;
+use std::io;
+
+#[cfg(all(
+    target_arch = "x86_64",
+    any(target_os = "windows", target_os = "linux")
+))]
+use crate::ptr::HookError;
+use crate::ptr::ParsePatternError;
+
+/// The ways the functions in [`ptr`](crate::ptr) can fail.
+///
+/// A panic inside a game process takes the whole game down with it, so nothing in this crate
+/// panics on a failure it can report instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The current platform is not supported.
+    Unsupported,
+    /// A call to the operating system failed with the error `code`.
+    Os { code: i32 },
+    /// The headers of an executable image are missing or malformed.
+    InvalidHeader,
+    /// No loaded module has the name.
+    ModuleNotFound,
+    /// The pattern does not occur in the scanned memory.
+    PatternNotFound,
+    /// The pattern occurs `count` times instead of once.
+    AmbiguousPattern { count: usize },
+    /// The pattern could not be built.
+    InvalidPattern(ParsePatternError),
+    /// The protection of the memory could not be changed, the operating system failed with the
+    /// error `code`.
+    Protect { code: i32 },
+    /// A hook could not be installed.
+    #[cfg(all(
+        target_arch = "x86_64",
+        any(target_os = "windows", target_os = "linux")
+    ))]
+    Hook(HookError),
+}
+
+impl Error {
+    /// Returns the error of the last call to the operating system on this thread.
+    #[cfg(target_os = "windows")]
+    pub(crate) fn last_os_error() -> Self {
+        io::Error::last_os_error().into()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => f.write_str("the platform is not supported"),
+            Self::Os { code } => write!(f, "the operating system failed with error {code}"),
+            Self::InvalidHeader => f.write_str("invalid executable image header"),
+            Self::ModuleNotFound => f.write_str("no module with the name is loaded"),
+            Self::PatternNotFound => f.write_str("the pattern was not found"),
+            Self::AmbiguousPattern { count } => {
+                write!(f, "the pattern was found {count} times instead of once")
+            }
+            Self::InvalidPattern(error) => write!(f, "invalid pattern: {error}"),
+            Self::Protect { code } => write!(
+                f,
+                "failed to change the memory protection, the operating system failed with error {code}"
+            ),
+            #[cfg(all(
+                target_arch = "x86_64",
+                any(target_os = "windows", target_os = "linux")
+            ))]
+            Self::Hook(error) => write!(f, "failed to install the hook: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(all(
+                target_arch = "x86_64",
+                any(target_os = "windows", target_os = "linux")
+            ))]
+            Self::Hook(error) => Some(error),
+            Self::InvalidPattern(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Os {
+            code: error.raw_os_error().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<ParsePatternError> for Error {
+    fn from(error: ParsePatternError) -> Self {
+        Self::InvalidPattern(error)
+    }
+}
+
+#[cfg(all(
+    target_arch = "x86_64",
+    any(target_os = "windows", target_os = "linux")
+))]
+impl From<HookError> for Error {
+    fn from(error: HookError) -> Self {
+        Self::Hook(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Error::AmbiguousPattern { count: 3 }.to_string(),
+            "the pattern was found 3 times instead of once"
+        );
+        assert_eq!(
+            Error::Os { code: 5 }.to_string(),
+            "the operating system failed with error 5"
+        );
+        assert_eq!(
+            Error::from(ParsePatternError::Empty).to_string(),
+            "invalid pattern: pattern is empty"
+        );
+    }
+
+    #[test]
+    fn from_io() {
+        let error = io::Error::from_raw_os_error(13);
+        assert_eq!(Error::from(error), Error::Os { code: 13 });
+    }
+
+    #[test]
+    fn from_parse_pattern() {
+        let error = Error::from(ParsePatternError::MissingMask);
+        assert_eq!(error, Error::InvalidPattern(ParsePatternError::MissingMask));
+        assert!(error.source().is_some());
+        assert!(Error::PatternNotFound.source().is_none());
+    }
+
+    #[cfg(all(
+        target_arch = "x86_64",
+        any(target_os = "windows", target_os = "linux")
+    ))]
+    #[test]
+    fn from_hook() {
+        let error = Error::from(HookError::TooShort { len: 3 });
+        assert_eq!(error, Error::Hook(HookError::TooShort { len: 3 }));
+        assert_eq!(
+            error.to_string(),
+            "failed to install the hook: function ends after 3 bytes, before there is room for the jump"
+        );
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            HookError::TooShort { len: 3 }.to_string()
+        );
+    }
+}
@@ -1 +1,4 @@
+mod error;
 pub mod ptr;
+
+pub use error::Error;
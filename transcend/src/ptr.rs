@@ -1,32 +1,77 @@
-use rayon::iter::IndexedParallelIterator;
-use rayon::slice::ParallelSlice;
-use std::ffi::CStr;
-use std::mem::zeroed;
-use std::ptr::copy_nonoverlapping;
 use std::slice::from_raw_parts;
-use std::{mem::transmute_copy, sync::LazyLock};
-use windows::Win32::Foundation::HMODULE;
-use windows::Win32::System::Diagnostics::Debug::{IMAGE_NT_HEADERS64, IMAGE_SECTION_HEADER};
-use windows::Win32::System::Memory::{
-    VirtualAlloc, VirtualProtect, MEM_COMMIT, MEM_RESERVE, PAGE_EXECUTE_READWRITE,
+use std::sync::LazyLock;
+
+use crate::Error;
+
+#[cfg(target_os = "windows")]
+use {
+    std::mem::zeroed,
+    windows::Win32::Foundation::HMODULE,
+    windows::Win32::System::ProcessStatus::{GetModuleInformation, MODULEINFO},
+    windows::Win32::System::Threading::GetCurrentProcess,
+};
+
+mod address;
+#[cfg(target_os = "linux")]
+mod elf;
+#[cfg(all(
+    target_arch = "x86_64",
+    any(target_os = "windows", target_os = "linux")
+))]
+mod hook;
+#[cfg(target_os = "linux")]
+mod maps;
+mod module;
+mod pattern;
+mod pe;
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+mod protect;
+mod scan;
+
+pub use address::{Address, Rva};
+#[cfg(all(
+    target_arch = "x86_64",
+    any(target_os = "windows", target_os = "linux")
+))]
+pub use hook::{
+    breakpoint_hook, mid_hook, page_hook, Context, Detour, ExceptionHook, HookError, MidHook,
+    Patch, ShadowVmt, Transaction, VmtHook,
 };
-use windows::Win32::System::ProcessStatus::{GetModuleInformation, MODULEINFO};
-use windows::Win32::System::SystemServices::IMAGE_DOS_HEADER;
-use windows::Win32::System::Threading::GetCurrentProcess;
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+pub use hook::{got_hook, GotHook};
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+pub use hook::{iat_hook, IatHook};
+pub use module::{module, modules, Module};
+pub use pattern::{ParsePatternError, Pattern};
+pub use pe::{imports, Import};
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+pub use protect::{ProtectGuard, Protection};
+pub use scan::{scan, scan_all, scan_iter, scan_many, scan_many_all, scan_unique};
 
 #[cfg(feature = "macros")]
 pub use transcend_macros::sig;
 
-// TODO: document
-// Get the base of the current process
-#[must_use]
+/// Returns the address the executable of the current process is loaded at, the start of its
+/// image.
+///
+/// The address is looked up once and cached, adding an [`Rva`] to it gives the address of anything
+/// in the executable:
+///
+/// ```no_run
+/// use transcend::ptr::{base, Rva};
+///
+/// # fn main() -> Result<(), transcend::Error> {
+/// let health = base()? + Rva::new(0x1F2D40);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// [`Error::Os`] on Windows or [`Error::InvalidHeader`] on Linux if the loader does not know the
+/// executable, [`Error::Unsupported`] on other platforms.
 #[inline(always)]
-pub fn base() -> *const usize {
-    struct Base(*const usize);
-    unsafe impl Send for Base {}
-    unsafe impl Sync for Base {}
-
-    static BASE: LazyLock<Base> = LazyLock::new(|| {
+pub fn base() -> Result<Address, Error> {
+    static BASE: LazyLock<Result<Address, Error>> = LazyLock::new(|| {
         #[cfg(target_os = "windows")]
         {
             use windows::core::PCWSTR;
@@ -34,10 +79,9 @@ pub fn base() -> *const usize {
 
             // SAFETY: `GetModuleHandleW(null)` returns a handle to the current process, which is (presumably) always valid for the lifetime of the process.
             // https://learn.microsoft.com/en-us/windows/win32/api/libloaderapi/nf-libloaderapi-getmodulehandlew
-            let address =
-                unsafe { GetModuleHandleW(PCWSTR::null()).unwrap_unchecked().0 as *const usize };
-
-            Base(address)
+            unsafe { GetModuleHandleW(PCWSTR::null()) }
+                .map(|module| Address::from_ptr(module.0))
+                .map_err(|_| Error::last_os_error())
         }
 
         #[cfg(target_os = "linux")]
@@ -45,182 +89,94 @@ pub fn base() -> *const usize {
             use core::mem::zeroed;
             use libc::{dladdr, getauxval, Dl_info, AT_PHDR};
 
-            let address = {
-                let mut info: Dl_info = unsafe { zeroed() };
-                let dummy_address = unsafe { getauxval(AT_PHDR) as *const usize };
-                unsafe { dladdr(dummy_address.cast(), &mut info) };
-                info.dli_fbase as *const usize
-            };
+            let mut info: Dl_info = unsafe { zeroed() };
+            let dummy_address = unsafe { getauxval(AT_PHDR) as *const usize };
 
-            Base(address)
+            match unsafe { dladdr(dummy_address.cast(), &mut info) } {
+                0 => Err(Error::InvalidHeader),
+                _ if info.dli_fbase.is_null() => Err(Error::InvalidHeader),
+                _ => Ok(Address::from_ptr(info.dli_fbase)),
+            }
         }
 
         #[cfg(not(any(target_os = "windows", target_os = "linux")))]
         {
-            unimplemented!()
+            Err(Error::Unsupported)
         }
     });
 
-    BASE.0
+    BASE.clone()
 }
 
-// TODO: document
-// Get the size of the current process
-#[must_use]
-pub fn size() -> usize {
+/// Returns the size in bytes of the image of the executable, from [`base`] to the end of its last
+/// section or segment.
+///
+/// Together with [`base`] this covers everything the loader mapped for the executable, which
+/// [`program`] returns as a slice.
+///
+/// # Errors
+/// The errors of [`base`], and [`Error::Os`] if Windows can not describe the image.
+pub fn size() -> Result<usize, Error> {
     #[cfg(target_os = "windows")]
     {
         let process = unsafe { GetCurrentProcess() };
-        let module = HMODULE(base() as *mut _);
+        let module = HMODULE(base()?.as_mut_ptr());
         let mut info = unsafe { zeroed() };
 
-        unsafe {
-            GetModuleInformation(process, module, &mut info, size_of::<MODULEINFO>() as u32)
-                .unwrap()
-        };
-        info.SizeOfImage as usize
-    }
+        unsafe { GetModuleInformation(process, module, &mut info, size_of::<MODULEINFO>() as u32) }
+            .map_err(|_| Error::last_os_error())?;
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        unimplemented!()
+        Ok(info.SizeOfImage as usize)
     }
-}
-
-#[must_use]
-pub fn program() -> &'static [u8] {
-    unsafe { from_raw_parts(base() as *const _, size()) }
-}
-
-pub fn scan(slice: &[u8], pattern: &[u8]) -> Option<*const usize> {
-    slice
-        .par_windows(pattern.len())
-        .position_first(|window| {
-            pattern
-                .iter()
-                .enumerate()
-                .all(|(i, &p)| p == 0xFF || window[i] == p)
-        })
-        .map(|offset| unsafe { slice.as_ptr().add(offset) as *const _ })
-}
 
-// x64 windows hook with trampoline using naked ASM
-pub fn hook<A>(target: *const usize, function: impl Fn(A)) {
-    let original_bytes = unsafe { from_raw_parts(target, 14) };
-    let original_size = 14;
-
-    let func = &function as *const _ as *const usize;
-
-    let mut old_protection = windows::Win32::System::Memory::PAGE_PROTECTION_FLAGS(0);
-    unsafe {
-        VirtualProtect(
-            target as *mut _,
-            original_size,
-            PAGE_EXECUTE_READWRITE,
-            &mut old_protection,
-        )
-        .unwrap();
+    #[cfg(target_os = "linux")]
+    {
+        let (bias, headers) = elf::program_headers();
+        Ok(elf::image_end(bias, headers) - base()?.as_usize())
     }
 
-    let trampoline = unsafe {
-        VirtualAlloc(None, 5, MEM_COMMIT | MEM_RESERVE, PAGE_EXECUTE_READWRITE) as *mut usize
-    };
-
-    unsafe { copy_nonoverlapping(target, trampoline, original_bytes.len()) };
-
-    let return_address = unsafe { target.add(original_bytes.len()) };
-    let trampoline_jump = unsafe { trampoline.add(original_bytes.len()) };
-
-    // Create a far jump back to the original code (after our hook)
-    let jump_back = [
-        0xFF, 0x25, // jmp [rip+offset]
-        0x00, 0x00, 0x00, 0x00, // offset placeholder (rip-relative offset to address)
-        0x00, 0x00, 0x00, 0x00, // address placeholder (64-bit address to jump to)
-    ];
-
-    unsafe { copy_nonoverlapping(jump_back.as_ptr(), trampoline_jump, jump_back.len()) };
-
-    let return_addr_location = unsafe { trampoline_jump.add(6) } as *mut *const usize;
-    unsafe { *return_addr_location = return_address };
-
-    // Now overwrite the target function with a jump to the hook function
-    let jump_instructions = [
-        0x48, 0xB8, // mov rax, <64-bit address>
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Placeholder for the address
-        0xFF, 0xE0, // jmp rax
-    ];
-
-    unsafe {
-        copy_nonoverlapping(
-            jump_instructions.as_ptr(),
-            target as *mut usize,
-            jump_instructions.len(),
-        )
-    };
-
-    // Write the actual 64-bit address of the hook function
-    let address_location = unsafe { target.add(2) } as *mut usize;
-    unsafe { *address_location = func as usize };
-
-    // Fill any remaining space with NOPs (No Operation instructions)
-    for i in jump_instructions.len()..original_size {
-        unsafe { *target.add(i) = 0x90 }; // NOP instruction
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    {
+        Err(Error::Unsupported)
     }
+}
 
-    // Restore the original memory protection
-    unsafe {
-        VirtualProtect(
-            target as *mut _,
-            original_size,
-            old_protection,
-            &mut old_protection,
-        )
-    };
+pub fn program() -> Result<&'static [u8], Error> {
+    Ok(unsafe { from_raw_parts(base()?.as_ptr(), size()?) })
 }
 
 #[derive(Debug)]
 pub struct Section {
     pub name: String,
-    pub base: *const usize,
+    pub base: Address,
     pub len: usize,
 }
 
 impl Section {
     pub fn as_slice(&self) -> &[u8] {
-        unsafe { from_raw_parts(self.base as *const _, self.len) }
+        unsafe { from_raw_parts(self.base.as_ptr(), self.len) }
     }
 }
 
-pub fn sections() -> Vec<Section> {
-    let base = base();
-
-    let dos_header = unsafe { &*(base as *const IMAGE_DOS_HEADER) };
-    let nt_headers =
-        unsafe { &*((base as usize + dos_header.e_lfanew as usize) as *const IMAGE_NT_HEADERS64) };
-
-    let section_header_ptr =
-        (base as usize + dos_header.e_lfanew as usize + size_of::<IMAGE_NT_HEADERS64>())
-            as *const IMAGE_SECTION_HEADER;
+pub fn sections() -> Result<Vec<Section>, Error> {
+    #[cfg(target_os = "windows")]
+    {
+        // SAFETY: The program itself stays mapped for the lifetime of the process.
+        unsafe { pe::sections(base()?) }.ok_or(Error::InvalidHeader)
+    }
 
-    (0..nt_headers.FileHeader.NumberOfSections)
-        .map(|index| unsafe { &*section_header_ptr.add(index as usize) })
-        .map(|section| {
-            let name = unsafe {
-                CStr::from_ptr(section.Name.as_ptr() as *const i8)
-                    .to_string_lossy()
-                    .into_owned()
-            };
+    #[cfg(target_os = "linux")]
+    {
+        elf::sections("/proc/self/exe", base()?)
+    }
 
-            Section {
-                name,
-                base: unsafe { base.add(section.VirtualAddress as usize) },
-                len: unsafe { section.Misc.VirtualSize as usize },
-            }
-        })
-        .collect()
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    {
+        Err(Error::Unsupported)
+    }
 }
 
-/// Calculates the offset from the base address of the calling process (.exe file).
+/// Resolves an offset from the base address of the calling process (.exe file) to a function.
 ///
 /// # Safety
 /// If any of the following conditions are violated, the result is Undefined Behavior:
@@ -228,19 +184,22 @@ pub fn sections() -> Vec<Section> {
 /// - The resulting function pointer from the computed offset must point to a function with the same signature as `F`.
 ///
 /// # Examples
-/// ```
+/// ```no_run
+/// use transcend::ptr::{resolve_rva, Rva};
+///
 /// type Add = unsafe extern "C" fn(u32, u32) -> u32;
 ///
-/// let offset = 0x2843A0;
-/// let add: Add = resolve_fn(offset);
+/// # fn main() -> Result<(), transcend::Error> {
+/// let add: Add = unsafe { resolve_rva(Rva::new(0x2843A0))? };
 ///
 /// unsafe { assert_eq!(2, add(1, 1)); }
+/// # Ok(())
+/// # }
 /// ```
-#[must_use]
 #[inline(always)]
-pub unsafe fn resolve_rva<F: FnPtr>(offset: usize) -> F {
+pub unsafe fn resolve_rva<F: FnPtr>(rva: Rva) -> Result<F, Error> {
     // SAFETY: The caller guarantees that `F` is an `unsafe extern "ABI" fn`.
-    unsafe { transmute_copy(&base().add(offset)) }
+    Ok(unsafe { (base()? + rva).as_fn() })
 }
 
 pub trait FnPtr {}
@@ -249,9 +208,37 @@ macro_rules! impl_fnptr {
     ($(($($args:ident),*)),*) => {
         $(
             impl<R, $($args),*> FnPtr for unsafe extern "C" fn($($args),*) -> R {}
-            impl<R, $($args),*> FnPtr for unsafe extern "cdecl" fn($($args),*) -> R {}
             impl<R, $($args),*> FnPtr for unsafe extern "win64" fn($($args),*) -> R {}
+
+            #[cfg(all(target_arch = "x86_64", any(target_os = "windows", target_os = "linux")))]
+            impl<R, $($args),*> Detour<unsafe extern "C" fn($($args),*) -> R> {
+                /// Calls the original function through the trampoline.
+                ///
+                /// # Safety
+                /// The same as calling the original function.
+                #[allow(non_snake_case)]
+                pub unsafe fn call_original(&self, $($args: $args),*) -> R {
+                    unsafe { (self.original())($($args),*) }
+                }
+            }
+            #[cfg(all(target_arch = "x86_64", any(target_os = "windows", target_os = "linux")))]
+            impl<R, $($args),*> Detour<unsafe extern "win64" fn($($args),*) -> R> {
+                /// Calls the original function through the trampoline.
+                ///
+                /// # Safety
+                /// The same as calling the original function.
+                #[allow(non_snake_case)]
+                pub unsafe fn call_original(&self, $($args: $args),*) -> R {
+                    unsafe { (self.original())($($args),*) }
+                }
+            }
+
+            // These conventions only exist on 32-bit x86, everywhere else they are rejected or alias "C".
+            #[cfg(target_arch = "x86")]
+            impl<R, $($args),*> FnPtr for unsafe extern "cdecl" fn($($args),*) -> R {}
+            #[cfg(target_arch = "x86")]
             impl<R, $($args),*> FnPtr for unsafe extern "fastcall" fn($($args),*) -> R {}
+            #[cfg(target_arch = "x86")]
             impl<R, $($args),*> FnPtr for unsafe extern "thiscall" fn($($args),*) -> R {}
         )*
     };
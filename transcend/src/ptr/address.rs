@@ -0,0 +1,374 @@
+use std::fmt;
+use std::mem::transmute_copy;
+use std::ops::{Add, Sub};
+use std::ptr::read_unaligned;
+
+use super::FnPtr;
+
+/// An absolute address in the current process.
+///
+/// Arithmetic is always in bytes, and the resolving helpers chain off a scan result:
+///
+/// ```no_run
+/// use transcend::ptr::{program, scan, Pattern};
+///
+/// # fn main() -> Result<(), transcend::Error> {
+/// let call: Pattern = "E8 ?? ?? ?? ?? 48 8B D8".parse().unwrap();
+/// let function = unsafe { scan(program()?, &call)?.call_target() };
+///
+/// let lea: Pattern = "48 8D 0D ?? ?? ?? ?? E8".parse().unwrap();
+/// let global = unsafe { scan(program()?, &lea)?.rip_relative(3, 7).deref() };
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Address(usize);
+
+impl Address {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(address: usize) -> Self {
+        Self(address)
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub fn from_ptr<T: ?Sized>(ptr: *const T) -> Self {
+        Self(ptr.cast::<()>() as usize)
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn as_usize(self) -> usize {
+        self.0
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn as_ptr<T>(self) -> *const T {
+        self.0 as *const T
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn as_mut_ptr<T>(self) -> *mut T {
+        self.0 as *mut T
+    }
+
+    /// Advances the address by `count` bytes.
+    #[must_use]
+    #[inline(always)]
+    #[allow(clippy::should_implement_trait)]
+    pub const fn add(self, count: usize) -> Self {
+        Self(self.0.wrapping_add(count))
+    }
+
+    /// Moves the address back by `count` bytes.
+    #[must_use]
+    #[inline(always)]
+    #[allow(clippy::should_implement_trait)]
+    pub const fn sub(self, count: usize) -> Self {
+        Self(self.0.wrapping_sub(count))
+    }
+
+    /// Moves the address by a signed amount of bytes.
+    #[must_use]
+    #[inline(always)]
+    pub const fn offset(self, count: isize) -> Self {
+        Self(self.0.wrapping_add_signed(count))
+    }
+
+    /// Returns the offset of this address from `base`, the start of the module that contains it.
+    #[must_use]
+    #[inline(always)]
+    pub const fn rva(self, base: Self) -> Rva {
+        Rva(self.0.wrapping_sub(base.0))
+    }
+
+    /// Reinterprets the address as a function pointer.
+    ///
+    /// # Safety
+    /// A function with the signature `F` must start at this address.
+    #[must_use]
+    #[inline(always)]
+    pub unsafe fn as_fn<F: FnPtr>(self) -> F {
+        // SAFETY: `FnPtr` is only implemented for function pointers, which have the size of an
+        // address.
+        unsafe { transmute_copy(&self.0) }
+    }
+
+    /// Reads a `T` from this address.
+    ///
+    /// # Safety
+    /// The address must be valid for reads of `size_of::<T>()` bytes, it does not need to be aligned.
+    #[must_use]
+    #[inline(always)]
+    pub unsafe fn read<T: Copy>(self) -> T {
+        unsafe { read_unaligned(self.as_ptr()) }
+    }
+
+    /// Reads the pointer stored at this address, e.g. to follow a global that holds an object.
+    ///
+    /// # Safety
+    /// The address must be valid for reads of `size_of::<usize>()` bytes.
+    #[must_use]
+    #[inline(always)]
+    pub unsafe fn deref(self) -> Self {
+        Self(unsafe { self.read() })
+    }
+
+    /// Resolves a RIP-relative operand of the instruction starting at this address.
+    ///
+    /// The 32-bit displacement at `displacement` bytes into the instruction is relative to the end of
+    /// the instruction, which is `len` bytes long. For `48 8D 0D ?? ?? ?? ??` (`lea rcx, [rip+x]`) that
+    /// is `rip_relative(3, 7)`.
+    ///
+    /// # Safety
+    /// The address must point to an instruction with a 32-bit displacement at the given offset.
+    #[must_use]
+    #[inline(always)]
+    pub unsafe fn rip_relative(self, displacement: usize, len: usize) -> Self {
+        let displacement: i32 = unsafe { self.add(displacement).read() };
+        self.add(len).offset(displacement as isize)
+    }
+
+    /// Resolves the target of a `call rel32` (`E8`) or `jmp rel32` (`E9`) starting at this address.
+    ///
+    /// # Safety
+    /// The address must point to a `call` or `jmp` with a 32-bit displacement.
+    #[must_use]
+    #[inline(always)]
+    pub unsafe fn call_target(self) -> Self {
+        unsafe { self.rip_relative(1, 5) }
+    }
+}
+
+impl Add<usize> for Address {
+    type Output = Self;
+
+    fn add(self, count: usize) -> Self {
+        Address::add(self, count)
+    }
+}
+
+impl Sub<usize> for Address {
+    type Output = Self;
+
+    fn sub(self, count: usize) -> Self {
+        Address::sub(self, count)
+    }
+}
+
+impl Sub for Address {
+    type Output = usize;
+
+    /// Returns the distance in bytes between two addresses.
+    fn sub(self, other: Self) -> usize {
+        self.0.wrapping_sub(other.0)
+    }
+}
+
+impl Add<Rva> for Address {
+    type Output = Self;
+
+    fn add(self, rva: Rva) -> Self {
+        Address::add(self, rva.0)
+    }
+}
+
+impl<T> From<*const T> for Address {
+    fn from(ptr: *const T) -> Self {
+        Self::from_ptr(ptr)
+    }
+}
+
+impl<T> From<*mut T> for Address {
+    fn from(ptr: *mut T) -> Self {
+        Self::from_ptr(ptr)
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+/// An offset from the start of a module, the way disassemblers show addresses when the module is
+/// loaded at its preferred base.
+///
+/// Offsets stay the same wherever the module is loaded, add one to the base of the module for the
+/// [`Address`] in the current process:
+///
+/// ```no_run
+/// use transcend::ptr::{base, Rva};
+///
+/// # fn main() -> Result<(), transcend::Error> {
+/// let health = base()? + Rva::new(0x1F2D40);
+/// let health: f32 = unsafe { health.read() };
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Rva(usize);
+
+impl Rva {
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(rva: usize) -> Self {
+        Self(rva)
+    }
+
+    #[must_use]
+    #[inline(always)]
+    pub const fn as_usize(self) -> usize {
+        self.0
+    }
+
+    /// Advances the offset by `count` bytes.
+    #[must_use]
+    #[inline(always)]
+    #[allow(clippy::should_implement_trait)]
+    pub const fn add(self, count: usize) -> Self {
+        Self(self.0.wrapping_add(count))
+    }
+
+    /// Moves the offset back by `count` bytes.
+    #[must_use]
+    #[inline(always)]
+    #[allow(clippy::should_implement_trait)]
+    pub const fn sub(self, count: usize) -> Self {
+        Self(self.0.wrapping_sub(count))
+    }
+}
+
+impl Add<usize> for Rva {
+    type Output = Self;
+
+    fn add(self, count: usize) -> Self {
+        Rva::add(self, count)
+    }
+}
+
+impl Sub<usize> for Rva {
+    type Output = Self;
+
+    fn sub(self, count: usize) -> Self {
+        Rva::sub(self, count)
+    }
+}
+
+impl Sub for Rva {
+    type Output = usize;
+
+    /// Returns the distance in bytes between two offsets.
+    fn sub(self, other: Self) -> usize {
+        self.0.wrapping_sub(other.0)
+    }
+}
+
+impl fmt::Debug for Rva {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl fmt::Display for Rva {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for Rva {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for Rva {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Copies `code` to `at` bytes into a buffer of `int3`.
+    fn code(at: usize, code: &[u8]) -> [u8; 64] {
+        let mut buffer = [0xCC; 64];
+        buffer[at..at + code.len()].copy_from_slice(code);
+        buffer
+    }
+
+    #[test]
+    fn call_target() {
+        // call +0x10
+        let buffer = code(8, &[0xE8, 0x10, 0x00, 0x00, 0x00]);
+        let start = Address::from_ptr(buffer.as_ptr());
+        assert_eq!(unsafe { (start + 8).call_target() }, start + 0x1D);
+
+        // jmp -0x20
+        let buffer = code(40, &[0xE9, 0xE0, 0xFF, 0xFF, 0xFF]);
+        let start = Address::from_ptr(buffer.as_ptr());
+        assert_eq!(unsafe { (start + 40).call_target() }, start + 13);
+    }
+
+    #[test]
+    fn rip_relative() {
+        // mov rax, [rip - 0x27], which points at the pointer stored at the start of the buffer.
+        let mut buffer = code(32, &[0x48, 0x8B, 0x05, 0xD9, 0xFF, 0xFF, 0xFF]);
+        buffer[..8].copy_from_slice(&0x1234_5678_9ABC_DEF0usize.to_ne_bytes());
+
+        let start = Address::from_ptr(buffer.as_ptr());
+        let operand = unsafe { (start + 32).rip_relative(3, 7) };
+
+        assert_eq!(operand, start);
+        assert_eq!(
+            unsafe { operand.deref() },
+            Address::new(0x1234_5678_9ABC_DEF0)
+        );
+        assert_eq!(unsafe { (start + 35).read::<i32>() }, -0x27);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let base = Address::new(0x7FF6_0000_0000);
+        let address = base + Rva::new(0x1F2D40);
+
+        assert_eq!(address, Address::new(0x7FF6_001F_2D40));
+        assert_eq!(address.rva(base), Rva::new(0x1F2D40));
+        assert_eq!(address - base, 0x1F2D40);
+        assert_eq!(address.offset(-0x40), Address::new(0x7FF6_001F_2D00));
+        assert_eq!(address - 0x40, address.offset(-0x40));
+        assert_eq!((address + 0x10).sub(0x10), address);
+
+        let rva = Rva::new(0x1000);
+        assert_eq!(rva + 0x20, Rva::new(0x1020));
+        assert_eq!(rva - 0x20, Rva::new(0xFE0));
+        assert_eq!(Rva::new(0x1020) - rva, 0x20);
+        assert_eq!(rva.to_string(), "0x1000");
+        assert_eq!(format!("{address:X}"), "7FF6001F2D40");
+    }
+}
@@ -0,0 +1,162 @@
+//! Helpers for reading the ELF image of the current process.
+
+use super::{Address, Section};
+use crate::Error;
+use libc::{
+    getauxval, sysconf, Elf64_Ehdr, Elf64_Phdr, Elf64_Shdr, _SC_PAGESIZE, AT_PHDR, AT_PHNUM,
+    PT_LOAD, PT_PHDR,
+};
+use std::ffi::CStr;
+use std::fs;
+use std::path::Path;
+use std::ptr::read_unaligned;
+use std::slice::from_raw_parts;
+
+const ELFMAG: [u8; 4] = *b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const SHF_ALLOC: u64 = 0x2;
+
+/// Returns the load bias and program headers of the main executable.
+pub(crate) fn program_headers() -> (usize, &'static [Elf64_Phdr]) {
+    // SAFETY: The auxiliary vector always describes the program headers of the main executable,
+    // which the loader maps for the whole lifetime of the process.
+    let headers = unsafe {
+        let phdr = getauxval(AT_PHDR) as *const Elf64_Phdr;
+        let phnum = getauxval(AT_PHNUM) as usize;
+        from_raw_parts(phdr, phnum)
+    };
+
+    // The load bias is the distance between where the headers were linked and where they ended up.
+    // Executables without a `PT_PHDR` entry are not position independent and have no bias.
+    let bias = headers
+        .iter()
+        .find(|header| header.p_type == PT_PHDR)
+        .map_or(0, |header| {
+            headers.as_ptr() as usize - header.p_vaddr as usize
+        });
+
+    (bias, headers)
+}
+
+/// Returns the address one past the last byte of the highest `PT_LOAD` segment.
+pub(crate) fn image_end(bias: usize, headers: &[Elf64_Phdr]) -> usize {
+    headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .map(|header| bias + (header.p_vaddr + header.p_memsz) as usize)
+        .max()
+        .unwrap_or(bias)
+}
+
+/// Returns the address of the first page of the lowest `PT_LOAD` segment.
+pub(crate) fn image_start(bias: usize, headers: &[Elf64_Phdr]) -> usize {
+    headers
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD)
+        .map(|header| bias + header.p_vaddr as usize)
+        .min()
+        .map_or(bias, |start| start & !(page_size() - 1))
+}
+
+/// Reads the section headers of the ELF file at `path` and relocates the allocated ones to the image
+/// mapped at `base`.
+///
+/// Section headers are not part of any loadable segment, so they have to come from the file on disk.
+pub(crate) fn sections(path: impl AsRef<Path>, base: Address) -> Result<Vec<Section>, Error> {
+    let file = fs::read(path)?;
+    parse_sections(&file, base).ok_or(Error::InvalidHeader)
+}
+
+fn parse_sections(file: &[u8], base: Address) -> Option<Vec<Section>> {
+    let header: Elf64_Ehdr = read(file, 0)?;
+
+    if header.e_ident[..4] != ELFMAG || header.e_ident[4] != ELFCLASS64 {
+        return None;
+    }
+
+    // Offsets and counts come straight from the file, a broken one must not overflow.
+    let entry = |table: u64, index: usize, len: u16| -> Option<usize> {
+        index
+            .checked_mul(len as usize)?
+            .checked_add(usize::try_from(table).ok()?)
+    };
+
+    // `base` is where the lowest segment got mapped, the bias is what got added to every address.
+    let headers = (0..header.e_phnum as usize)
+        .map(|index| read(file, entry(header.e_phoff, index, header.e_phentsize)?))
+        .collect::<Option<Vec<Elf64_Phdr>>>()?;
+    let bias = base.sub(image_start(0, &headers));
+
+    let section = |index: usize| -> Option<Elf64_Shdr> {
+        read(file, entry(header.e_shoff, index, header.e_shentsize)?)
+    };
+
+    let names = section(header.e_shstrndx as usize)?;
+
+    let sections = (0..header.e_shnum as usize)
+        .map(section)
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .filter(|section| section.sh_flags & SHF_ALLOC != 0 && section.sh_addr != 0)
+        .map(|section| {
+            let offset = names.sh_offset.checked_add(section.sh_name as u64)?;
+            let name = file
+                .get(usize::try_from(offset).ok()?..)
+                .and_then(|name| CStr::from_bytes_until_nul(name).ok())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            Some(Section {
+                name,
+                base: bias.add(section.sh_addr as usize),
+                len: section.sh_size as usize,
+            })
+        })
+        .collect::<Option<_>>()?;
+
+    Some(sections)
+}
+
+pub(crate) fn page_size() -> usize {
+    // SAFETY: `sysconf` has no preconditions.
+    unsafe { sysconf(_SC_PAGESIZE) as usize }
+}
+
+fn read<T: Copy>(bytes: &[u8], offset: usize) -> Option<T> {
+    let bytes = bytes.get(offset..offset.checked_add(size_of::<T>())?)?;
+
+    // SAFETY: The slice is exactly `size_of::<T>()` bytes long and `T` is a plain C struct.
+    Some(unsafe { read_unaligned(bytes.as_ptr().cast()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::offset_of;
+
+    /// The test binary with `value` written over the field at `offset` of its ELF header.
+    fn corrupt(offset: usize, value: &[u8]) -> Vec<u8> {
+        let mut file = fs::read("/proc/self/exe").unwrap();
+        file[offset..offset + value.len()].copy_from_slice(value);
+        file
+    }
+
+    #[test]
+    fn malformed_offsets() {
+        let file = fs::read("/proc/self/exe").unwrap();
+        let base = Address::new(0x5555_0000_0000);
+        assert!(parse_sections(&file, base).is_some_and(|sections| !sections.is_empty()));
+
+        for (offset, value) in [
+            (offset_of!(Elf64_Ehdr, e_shoff), &u64::MAX.to_le_bytes()[..]),
+            (offset_of!(Elf64_Ehdr, e_phoff), &u64::MAX.to_le_bytes()[..]),
+            (
+                offset_of!(Elf64_Ehdr, e_shentsize),
+                &u16::MAX.to_le_bytes()[..],
+            ),
+            (offset_of!(Elf64_Ehdr, e_shnum), &u16::MAX.to_le_bytes()[..]),
+        ] {
+            assert!(parse_sections(&corrupt(offset, value), base).is_none());
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use core::fmt;
+use std::marker::PhantomData;
+use std::mem::transmute_copy;
+use std::ptr::copy_nonoverlapping;
+use std::slice::from_raw_parts;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(target_os = "windows")]
+use windows::Win32::System::{
+    Diagnostics::Debug::FlushInstructionCache, Threading::GetCurrentProcess,
+};
+
+use super::protect::{protect, readable, regions, Protection, Region};
+use super::FnPtr;
+use crate::Error;
+use decode::{decode, Flow, MAX_LEN};
+use relocate::{relocate, Relocated};
+
+pub use exception::{breakpoint_hook, page_hook, ExceptionHook};
+#[cfg(target_os = "linux")]
+pub use got::{got_hook, GotHook};
+#[cfg(target_os = "windows")]
+pub use iat::{iat_hook, IatHook};
+pub use mid::{mid_hook, Context, MidHook};
+pub use patch::Patch;
+pub use transaction::Transaction;
+pub use vmt::{ShadowVmt, VmtHook};
+
+mod allocator;
+mod decode;
+mod entry;
+mod exception;
+#[cfg(target_os = "linux")]
+mod got;
+#[cfg(target_os = "windows")]
+mod iat;
+mod mid;
+mod patch;
+mod relocate;
+mod threads;
+mod transaction;
+mod vmt;
+
+/// `jmp [rip + 0]` followed by the absolute address, which reaches anywhere in the address space.
+const JUMP_LEN: usize = 14;
+
+/// `jmp rel32`, which reaches ±2 GiB.
+const NEAR_JUMP_LEN: usize = 5;
+
+/// How far past the stolen bytes to look for branches that land inside them.
+const SCAN_LEN: usize = 256;
+
+/// The ways installing a hook can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookError {
+    /// The instruction `offset` bytes into the target could not be decoded.
+    Decode { offset: usize },
+    /// The function returns or jumps away after `len` bytes, before there is room for the jump.
+    TooShort { len: usize },
+    /// The instruction `from` bytes into the target branches to `to` bytes into the target, which
+    /// would land in the middle of the jump.
+    BranchIntoPrologue { from: usize, to: usize },
+    /// The instruction `offset` bytes into the target can not be moved into the trampoline.
+    Relocate { offset: usize },
+    /// The trampoline could not be allocated, or too many hooks are installed.
+    Allocate,
+    /// The protection of the target could not be changed.
+    Protect,
+    /// The other threads of the process could not be stopped.
+    Suspend,
+    /// The module does not import the function by name.
+    NotImported,
+    /// The exception handler could not be installed.
+    Handler,
+    /// Entry `index` is past the `len` entries of a vtable copy.
+    Index { index: usize, len: usize },
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode { offset } => write!(f, "invalid instruction at offset {offset:#x}"),
+            Self::TooShort { len } => write!(
+                f,
+                "function ends after {len} bytes, before there is room for the jump"
+            ),
+            Self::BranchIntoPrologue { from, to } => write!(
+                f,
+                "instruction at offset {from:#x} branches into the hooked bytes at offset {to:#x}"
+            ),
+            Self::Relocate { offset } => write!(
+                f,
+                "instruction at offset {offset:#x} can not be moved into the trampoline"
+            ),
+            Self::Allocate => f.write_str("failed to allocate the trampoline"),
+            Self::Protect => f.write_str("failed to change the memory protection of the target"),
+            Self::Suspend => f.write_str("failed to stop the other threads"),
+            Self::NotImported => f.write_str("the module does not import the function"),
+            Self::Handler => f.write_str("failed to install the exception handler"),
+            Self::Index { index, len } => {
+                write!(f, "entry {index} is past the {len} copied entries")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// Decodes the instruction `offset` bytes into `code`.
+fn instruction(code: &[u8], offset: usize) -> Result<decode::Instruction, HookError> {
+    code.get(offset..)
+        .and_then(decode)
+        .ok_or(HookError::Decode { offset })
+}
+
+/// Returns how many bytes at the start of `target` have to be moved to make room for a jump of
+/// `needed` bytes, always ending on an instruction boundary.
+///
+/// Nothing past the end of the readable memory that contains `target` is decoded.
+///
+/// # Safety
+/// `target` must point to the start of a function.
+unsafe fn stolen_len(target: *const u8, needed: usize) -> Result<usize, HookError> {
+    // The last stolen instruction starts before `needed`, the last scanned one before `SCAN_LEN`
+    // bytes past it.
+    let limit = needed + MAX_LEN + SCAN_LEN + MAX_LEN;
+    // SAFETY: The pages were just checked to be readable.
+    let code = unsafe { from_raw_parts(target, readable(target as usize, limit)) };
+
+    let mut len = 0;
+
+    while len < needed {
+        let instruction = instruction(code, len)?;
+        len += instruction.len;
+
+        if matches!(instruction.flow, Flow::Jump | Flow::Exit) && len < needed {
+            return Err(HookError::TooShort { len });
+        }
+    }
+
+    // Any branch from the rest of the function that lands between the first and the last stolen
+    // byte would execute half of the jump, branches among the stolen bytes are moved with them.
+    // Undecodable bytes and the end of the readable memory mark the end of what can be checked, as
+    // does the first return, padding `int3` or unconditional jump, after which the code may belong
+    // to another function.
+    let mut offset = len;
+    while offset < len + SCAN_LEN {
+        let Ok(instruction) = instruction(code, offset) else {
+            break;
+        };
+
+        let address = target as usize + offset;
+        if let Some(to) = instruction.branch_target(address, &code[offset..]) {
+            let to = to.wrapping_sub(target as usize);
+            if (1..len).contains(&to) {
+                return Err(HookError::BranchIntoPrologue { from: offset, to });
+            }
+        }
+
+        if matches!(instruction.flow, Flow::Jump | Flow::Exit) {
+            break;
+        }
+
+        offset += instruction.len;
+    }
+
+    Ok(len)
+}
+
+/// A function whose calls are redirected to a replacement, which can still reach the original
+/// through a trampoline.
+///
+/// The hook is removed and the trampoline freed when the detour is dropped, which makes it safe to
+/// unload the module that contains the replacement afterwards.
+///
+/// # Examples
+/// ```no_run
+/// use std::sync::OnceLock;
+/// use transcend::ptr::{resolve_rva, Detour, Rva};
+///
+/// type Damage = unsafe extern "C" fn(u32, f32) -> f32;
+///
+/// static DAMAGE: OnceLock<Detour<Damage>> = OnceLock::new();
+///
+/// unsafe extern "C" fn damage(entity: u32, amount: f32) -> f32 {
+///     // Forward to the game with twice the damage.
+///     unsafe { DAMAGE.get().unwrap().call_original(entity, amount * 2.0) }
+/// }
+///
+/// unsafe {
+///     let target: Damage = resolve_rva(Rva::new(0x2843A0)).unwrap();
+///     DAMAGE.get_or_init(|| Detour::new(target, damage).unwrap());
+/// }
+/// ```
+pub struct Detour<F: FnPtr> {
+    target: usize,
+    detour: usize,
+    /// Holds a jump to the detour followed by the trampoline.
+    slot: usize,
+    prologue: Prologue,
+    enabled: Mutex<bool>,
+    function: PhantomData<F>,
+}
+
+impl<F: FnPtr> Detour<F> {
+    /// Redirects `target` to `detour`.
+    ///
+    /// The trampoline is placed within ±2 GiB of `target` whenever there is free memory, so a
+    /// 5-byte `jmp rel32` suffices, otherwise a 14-byte absolute jump is used. Whole instructions
+    /// at the start of `target` are moved into the trampoline until there is room for the jump.
+    /// Relative branches and RIP-relative operands among them are rewritten to still reach their
+    /// targets from there. Functions that end before that or that branch back into the moved bytes
+    /// are refused.
+    ///
+    /// # Safety
+    /// `target` must point to the start of a function and no thread may be executing its first
+    /// bytes while the hook is installed.
+    ///
+    /// Branches back into the moved bytes are only looked for in the 256 bytes after them, up to
+    /// the first return or unconditional jump and the end of the readable memory. Code further on,
+    /// like a loop behind an early return, must not branch into them either.
+    pub unsafe fn new(target: F, detour: F) -> Result<Self, Error> {
+        let hook = unsafe { Self::new_disabled(target, detour)? };
+        unsafe { hook.enable()? };
+        Ok(hook)
+    }
+
+    /// Prepares the trampoline like [`new`](Self::new) but leaves `target` untouched until the
+    /// detour is enabled, for example by a [`Transaction`].
+    ///
+    /// # Safety
+    /// `target` must point to the start of a function, which may not branch back into the moved
+    /// bytes beyond what [`new`](Self::new) checks.
+    pub unsafe fn new_disabled(target: F, detour: F) -> Result<Self, Error> {
+        // SAFETY: `FnPtr` is only implemented for function pointers.
+        let target = unsafe { transmute_copy::<F, usize>(&target) };
+        let detour = unsafe { transmute_copy::<F, usize>(&detour) };
+
+        let slot = allocator::allocate(target).ok_or(HookError::Allocate)?;
+
+        match unsafe { prepare(target as *mut u8, detour, slot) } {
+            Ok(prologue) => Ok(Self {
+                target,
+                detour,
+                slot,
+                prologue,
+                enabled: Mutex::new(false),
+                function: PhantomData,
+            }),
+            Err(error) => {
+                unsafe { allocator::free(slot) };
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Returns the hooked function, which leads to the detour while enabled.
+    #[must_use]
+    pub fn target(&self) -> F {
+        // SAFETY: `FnPtr` is only implemented for function pointers.
+        unsafe { transmute_copy(&self.target) }
+    }
+
+    /// Returns the trampoline that runs the original function.
+    #[must_use]
+    pub fn original(&self) -> F {
+        // SAFETY: The trampoline has the same signature as the target it was built from.
+        unsafe { transmute_copy(&(self.slot + JUMP_LEN)) }
+    }
+
+    /// Redirects the target to the detour.
+    ///
+    /// # Safety
+    /// No thread may be executing the first bytes of the target, use a [`Transaction`] to patch
+    /// code other threads may run.
+    pub unsafe fn enable(&self) -> Result<(), Error> {
+        let mut enabled = self.enabled.lock().unwrap_or_else(PoisonError::into_inner);
+        if !*enabled {
+            unsafe { write(self.target as *mut u8, &self.prologue.patch)? };
+            *enabled = true;
+        }
+
+        Ok(())
+    }
+
+    /// Restores the original code of the target, the trampoline stays usable.
+    ///
+    /// # Safety
+    /// No thread may be executing the first bytes of the target, use a [`Transaction`] to patch
+    /// code other threads may run.
+    pub unsafe fn disable(&self) -> Result<(), Error> {
+        let mut enabled = self.enabled.lock().unwrap_or_else(PoisonError::into_inner);
+        if *enabled {
+            unsafe { write(self.target as *mut u8, &self.prologue.original)? };
+            *enabled = false;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether calls to the target are redirected to the detour.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        *self.enabled.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<F: FnPtr> Drop for Detour<F> {
+    fn drop(&mut self) {
+        // The trampoline has to stay around if the target still jumps to it.
+        if unsafe { self.disable() }.is_ok() {
+            unsafe { allocator::free(self.slot) };
+        }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for Detour<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Detour")
+            .field("target", &(self.target as *const u8))
+            .field("detour", &(self.detour as *const u8))
+            .field(
+                "trampoline",
+                &(self.slot as *const u8).wrapping_add(JUMP_LEN),
+            )
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+/// The start of a hooked function.
+struct Prologue {
+    /// The bytes the jump to the detour replaces.
+    original: Box<[u8]>,
+    /// The jump to the detour.
+    patch: Box<[u8]>,
+    /// The offset of each replaced instruction and the address of its copy in the trampoline.
+    boundaries: Box<[(usize, usize)]>,
+}
+
+/// Fills `slot` with a jump to `detour` and the trampoline for `target`.
+///
+/// Returns the bytes the jump to the slot replaces and the jump itself.
+///
+/// # Safety
+/// See [`Detour::new_disabled`].
+unsafe fn prepare(target: *mut u8, detour: usize, slot: usize) -> Result<Prologue, HookError> {
+    let address = target as usize;
+
+    // A jump to the slot which then jumps to the detour if it is within reach, otherwise right to
+    // the detour.
+    let near = rel32(address + NEAR_JUMP_LEN, slot).is_some();
+    let len = unsafe { stolen_len(target, if near { NEAR_JUMP_LEN } else { JUMP_LEN })? };
+    let original: Box<[u8]> = unsafe { from_raw_parts(target, len) }.into();
+
+    let trampoline = slot + JUMP_LEN;
+    let mut code = jump(slot, detour);
+    code.resize(JUMP_LEN, 0xCC);
+    let relocated = relocate_back(&original, address, trampoline)?;
+    code.extend(relocated.code);
+
+    if code.len() > allocator::SLOT_LEN {
+        return Err(HookError::Allocate);
+    }
+
+    unsafe { copy_nonoverlapping(code.as_ptr(), slot as *mut u8, code.len()) };
+
+    // Pad the rest of the last moved instruction so disassemblers stay in sync.
+    let mut patch = jump(address, if near { slot } else { detour });
+    patch.resize(len, 0x90);
+
+    let boundaries = relocated
+        .boundaries
+        .into_iter()
+        .map(|(from, to)| (from, trampoline + to))
+        .collect();
+
+    Ok(Prologue {
+        original,
+        patch: patch.into(),
+        boundaries,
+    })
+}
+
+/// Moves `original`, the instructions at `address`, to `at` and follows them with a jump back to
+/// the rest of the function.
+fn relocate_back(original: &[u8], address: usize, at: usize) -> Result<Relocated, HookError> {
+    let mut relocated = relocate(original, address, at)?;
+    let end = at + relocated.code.len();
+    relocated.code.extend(jump(end, address + original.len()));
+    Ok(relocated)
+}
+
+/// Copies `bytes` over the code at `at`.
+///
+/// # Safety
+/// `at` must point to `bytes.len()` bytes of code that no thread is executing.
+unsafe fn write(at: *mut u8, bytes: &[u8]) -> Result<(), HookError> {
+    unsafe { Write::new(at, bytes)?.apply() }
+}
+
+/// A copy over code, prepared so applying it does not allocate while other threads are stopped.
+struct Write<'a> {
+    at: *mut u8,
+    bytes: &'a [u8],
+    /// The protection of the code afterwards, which can differ from page to page.
+    regions: Vec<Region>,
+}
+
+impl<'a> Write<'a> {
+    fn new(at: *mut u8, bytes: &'a [u8]) -> Result<Self, HookError> {
+        let regions = regions(at as usize, bytes.len()).map_err(|_| HookError::Protect)?;
+        Ok(Self { at, bytes, regions })
+    }
+
+    /// # Safety
+    /// The code may not be executed while it is being replaced.
+    unsafe fn apply(&self) -> Result<(), HookError> {
+        let (at, len) = (self.at, self.bytes.len());
+
+        unsafe { protect(at as usize, len, Protection::ReadWriteExecute.native()) }
+            .map_err(|_| HookError::Protect)?;
+
+        unsafe { copy(self.bytes, at) };
+
+        for region in &self.regions {
+            unsafe { protect(region.start, region.len, region.protection) }
+                .map_err(|_| HookError::Protect)?;
+        }
+
+        flush(at, len);
+        Ok(())
+    }
+}
+
+/// Makes sure the processor executes the code just written to `at`.
+fn flush(at: *const u8, len: usize) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = unsafe { FlushInstructionCache(GetCurrentProcess(), Some(at.cast()), len) };
+    }
+
+    // x86 keeps the instruction cache coherent, there is nothing to flush.
+    #[cfg(target_os = "linux")]
+    let _ = (at, len);
+}
+
+/// Copies `bytes` to `at`, aligned pointers in a single store so other threads never read half of
+/// one.
+///
+/// # Safety
+/// `at` must be valid for `bytes.len()` writes.
+unsafe fn copy(bytes: &[u8], at: *mut u8) {
+    match <[u8; size_of::<usize>()]>::try_from(bytes) {
+        Ok(value) if at.align_offset(align_of::<usize>()) == 0 => {
+            let at = unsafe { &*at.cast::<AtomicUsize>() };
+            at.store(usize::from_ne_bytes(value), Ordering::Release);
+        }
+        _ => unsafe { copy_nonoverlapping(bytes.as_ptr(), at, bytes.len()) },
+    }
+}
+
+/// Returns the displacement of a `rel32` operand from `end`, the address after the instruction,
+/// to `destination`.
+fn rel32(end: usize, destination: usize) -> Option<i32> {
+    i32::try_from(destination.wrapping_sub(end) as isize).ok()
+}
+
+/// Returns a jump at `at` to `destination`, `jmp rel32` if it is within reach and `jmp [rip + 0]`
+/// otherwise.
+fn jump(at: usize, destination: usize) -> Vec<u8> {
+    match rel32(at + NEAR_JUMP_LEN, destination) {
+        Some(displacement) => {
+            let mut jump = vec![0xE9];
+            jump.extend_from_slice(&displacement.to_le_bytes());
+            jump
+        }
+        None => {
+            let mut jump = vec![0xFF, 0x25, 0x00, 0x00, 0x00, 0x00];
+            jump.extend_from_slice(&destination.to_le_bytes());
+            jump
+        }
+    }
+}
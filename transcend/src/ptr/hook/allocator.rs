@@ -0,0 +1,298 @@
+//! Executable memory for trampolines close to the code they belong to.
+//!
+//! Blocks are reserved within ±2 GiB of a hooked function so a 5-byte `jmp rel32` reaches its
+//! trampoline, and are split into fixed slots which hooks near each other share.
+
+use std::sync::Mutex;
+
+#[cfg(target_os = "windows")]
+use windows::Win32::System::{
+    Memory::{
+        VirtualAlloc, VirtualFree, VirtualQuery, MEMORY_BASIC_INFORMATION, MEM_COMMIT, MEM_FREE,
+        MEM_RELEASE, MEM_RESERVE, PAGE_EXECUTE_READWRITE,
+    },
+    SystemInformation::{GetSystemInfo, SYSTEM_INFO},
+};
+
+#[cfg(target_os = "linux")]
+use {
+    super::super::maps,
+    libc::{
+        mmap, munmap, MAP_ANONYMOUS, MAP_FAILED, MAP_FIXED_NOREPLACE, MAP_PRIVATE, PROT_EXEC,
+        PROT_READ, PROT_WRITE,
+    },
+};
+
+/// The size of a slot, which fits a trampoline for any realistic prologue.
+pub(crate) const SLOT_LEN: usize = 256;
+
+/// The size of a block, the allocation granularity of Windows and a multiple of the page size
+/// everywhere else.
+const BLOCK_LEN: usize = 0x10000;
+
+/// How far a block may be from the address it is allocated near, so all of it stays within reach
+/// of a 32-bit displacement.
+const REACH: usize = 0x8000_0000 - BLOCK_LEN;
+
+static BLOCKS: Mutex<Vec<Block>> = Mutex::new(Vec::new());
+
+struct Block {
+    base: usize,
+    /// One bit per slot, set while it is in use.
+    used: [u64; BLOCK_LEN / SLOT_LEN / 64],
+}
+
+impl Block {
+    fn allocate(&mut self) -> Option<usize> {
+        let (word, bits) = self
+            .used
+            .iter_mut()
+            .enumerate()
+            .find(|(_, bits)| **bits != u64::MAX)?;
+
+        let bit = bits.trailing_ones() as usize;
+        *bits |= 1 << bit;
+
+        Some(self.base + (word * 64 + bit) * SLOT_LEN)
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        (self.base..self.base + BLOCK_LEN).contains(&address)
+    }
+}
+
+/// Returns a slot of [`SLOT_LEN`] executable bytes, within reach of a `jmp rel32` from `near` if
+/// there is free address space around it.
+pub(crate) fn allocate(near: usize) -> Option<usize> {
+    let mut blocks = BLOCKS.lock().unwrap_or_else(|error| error.into_inner());
+
+    let close = |base: usize| base.abs_diff(near) <= REACH;
+
+    if let Some(slot) = blocks
+        .iter_mut()
+        .filter(|block| close(block.base))
+        .find_map(Block::allocate)
+    {
+        return Some(slot);
+    }
+
+    // SAFETY: Reserving fresh memory has no effect on existing code.
+    if let Some(base) = unsafe { reserve(near) } {
+        return push(&mut blocks, base);
+    }
+
+    // Without free space nearby any block will do.
+    if let Some(slot) = blocks.iter_mut().find_map(Block::allocate) {
+        return Some(slot);
+    }
+
+    let base = unsafe { reserve_anywhere() }?;
+    push(&mut blocks, base)
+}
+
+/// Adds the block at `base` and returns its first slot.
+fn push(blocks: &mut Vec<Block>, base: usize) -> Option<usize> {
+    let mut block = Block {
+        base,
+        used: [0; BLOCK_LEN / SLOT_LEN / 64],
+    };
+    let slot = block.allocate();
+    blocks.push(block);
+
+    slot
+}
+
+/// Returns a slot from [`allocate`], releasing its block once all slots are free.
+///
+/// # Safety
+/// Nothing may execute the slot anymore.
+pub(crate) unsafe fn free(slot: usize) {
+    let mut blocks = BLOCKS.lock().unwrap_or_else(|error| error.into_inner());
+
+    let Some(index) = blocks.iter().position(|block| block.contains(slot)) else {
+        return;
+    };
+
+    let block = &mut blocks[index];
+    let bit = (slot - block.base) / SLOT_LEN;
+    block.used[bit / 64] &= !(1 << (bit % 64));
+
+    if block.used.iter().all(|&bits| bits == 0) {
+        let block = blocks.swap_remove(index);
+        unsafe { release(block.base) };
+    }
+}
+
+/// Reserves a block in the closest free region around `near`, searching downwards first since
+/// images are usually loaded high with free space below.
+#[cfg(target_os = "windows")]
+unsafe fn reserve(near: usize) -> Option<usize> {
+    let mut info = SYSTEM_INFO::default();
+    unsafe { GetSystemInfo(&mut info) };
+
+    let minimum = (info.lpMinimumApplicationAddress as usize).max(near.saturating_sub(REACH));
+    let maximum = (info.lpMaximumApplicationAddress as usize).min(near.saturating_add(REACH));
+
+    let align = |address: usize| address & !(BLOCK_LEN - 1);
+
+    // Below, from the closest free region down.
+    let mut address = align(near);
+    while address >= minimum + BLOCK_LEN {
+        let region = unsafe { query(address - 1) }?;
+        let candidate = address - BLOCK_LEN;
+
+        if region.State == MEM_FREE && region.BaseAddress as usize <= candidate {
+            if let Some(base) = unsafe { commit(candidate) } {
+                return Some(base);
+            }
+        }
+
+        address = align(region.BaseAddress as usize);
+    }
+
+    // Above, from the closest free region up.
+    let mut address = align(near);
+    while address + BLOCK_LEN <= maximum {
+        let region = unsafe { query(address) }?;
+        let end = region.BaseAddress as usize + region.RegionSize;
+
+        if region.State == MEM_FREE && end >= address + BLOCK_LEN {
+            if let Some(base) = unsafe { commit(address) } {
+                return Some(base);
+            }
+        }
+
+        address = align(end + BLOCK_LEN - 1);
+    }
+
+    None
+}
+
+/// Reserves a block in the free gap between mappings that is closest to `near`.
+#[cfg(target_os = "linux")]
+unsafe fn reserve(near: usize) -> Option<usize> {
+    let minimum = near.saturating_sub(REACH).max(BLOCK_LEN);
+    let maximum = near.saturating_add(REACH);
+
+    let align_down = |address: usize| address & !(BLOCK_LEN - 1);
+    let align_up = |address: usize| align_down(address + BLOCK_LEN - 1);
+
+    let regions = maps::regions();
+    let starts = regions.iter().map(|region| region.range.start);
+    let ends = [0]
+        .into_iter()
+        .chain(regions.iter().map(|region| region.range.end));
+
+    // The block in each gap that is closest to `near`.
+    let mut candidates: Vec<usize> = ends
+        .zip(starts.chain([usize::MAX]))
+        .filter_map(|(start, end)| {
+            let low = align_up(start.max(minimum));
+            let high = align_down(end.min(maximum)).checked_sub(BLOCK_LEN)?;
+            (low <= high).then(|| align_down(near).clamp(low, high))
+        })
+        .collect();
+
+    candidates.sort_by_key(|candidate| candidate.abs_diff(near));
+    candidates
+        .into_iter()
+        .find_map(|candidate| unsafe { commit(candidate) })
+}
+
+/// Reserves a block wherever the system wants, which only allows absolute jumps.
+#[cfg(target_os = "windows")]
+unsafe fn reserve_anywhere() -> Option<usize> {
+    let base = unsafe {
+        VirtualAlloc(
+            None,
+            BLOCK_LEN,
+            MEM_COMMIT | MEM_RESERVE,
+            PAGE_EXECUTE_READWRITE,
+        )
+    };
+
+    (!base.is_null()).then_some(base as usize)
+}
+
+/// Reserves a block wherever the system wants, which only allows absolute jumps.
+#[cfg(target_os = "linux")]
+unsafe fn reserve_anywhere() -> Option<usize> {
+    let base = unsafe {
+        mmap(
+            std::ptr::null_mut(),
+            BLOCK_LEN,
+            PROT_READ | PROT_WRITE | PROT_EXEC,
+            MAP_PRIVATE | MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+
+    (base != MAP_FAILED).then_some(base as usize)
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn query(address: usize) -> Option<MEMORY_BASIC_INFORMATION> {
+    let mut region = MEMORY_BASIC_INFORMATION::default();
+    let len = unsafe {
+        VirtualQuery(
+            Some(address as *const _),
+            &mut region,
+            size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+
+    (len != 0).then_some(region)
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn commit(address: usize) -> Option<usize> {
+    let base = unsafe {
+        VirtualAlloc(
+            Some(address as *const _),
+            BLOCK_LEN,
+            MEM_COMMIT | MEM_RESERVE,
+            PAGE_EXECUTE_READWRITE,
+        )
+    };
+
+    (!base.is_null()).then_some(base as usize)
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn commit(address: usize) -> Option<usize> {
+    let base = unsafe {
+        mmap(
+            address as *mut _,
+            BLOCK_LEN,
+            PROT_READ | PROT_WRITE | PROT_EXEC,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE,
+            -1,
+            0,
+        )
+    };
+
+    if base == MAP_FAILED {
+        return None;
+    }
+
+    // Kernels before 4.17 treat the address as a hint and may map somewhere else.
+    if base as usize != address {
+        unsafe { munmap(base, BLOCK_LEN) };
+        return None;
+    }
+
+    Some(address)
+}
+
+unsafe fn release(base: usize) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = unsafe { VirtualFree(base as *mut _, 0, MEM_RELEASE) };
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        unsafe { munmap(base as *mut _, BLOCK_LEN) };
+    }
+}
@@ -0,0 +1,626 @@
+//! A length decoder for x86-64 instructions.
+//!
+//! It only decodes as much as hooking needs: how long an instruction is, whether it transfers control
+//! and where any RIP-relative displacement lives.
+
+/// How an instruction affects the flow of execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Flow {
+    /// Execution continues with the next instruction.
+    Next,
+    /// `jmp rel8` or `jmp rel32`.
+    Jump,
+    /// `jcc rel8` or `jcc rel32` with the condition code in the low nibble of the opcode.
+    Branch { condition: u8 },
+    /// `call rel32`.
+    Call,
+    /// `loop`, `loope`, `loopne` or `jrcxz`, which only exist with an 8-bit displacement.
+    Loop,
+    /// Execution never falls through to the next instruction, like `ret`, `jmp rax` or `int3`.
+    Exit,
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Instruction {
+    pub(crate) len: usize,
+    pub(crate) flow: Flow,
+    /// The offset and size of the displacement of a relative branch or call.
+    pub(crate) relative: Option<(usize, usize)>,
+    /// The offset of the displacement of a `[rip + disp32]` memory operand.
+    pub(crate) rip_relative: Option<usize>,
+}
+
+impl Instruction {
+    /// Returns the target of the relative branch or call encoded in `code` at `address`.
+    pub(crate) fn branch_target(&self, address: usize, code: &[u8]) -> Option<usize> {
+        let (offset, size) = self.relative?;
+
+        let displacement = match size {
+            1 => code[offset] as i8 as isize,
+            _ => read_i32(code, offset) as isize,
+        };
+
+        Some((address + self.len).wrapping_add_signed(displacement))
+    }
+
+    /// Returns the address referenced by the RIP-relative memory operand encoded in `code` at `address`.
+    pub(crate) fn rip_target(&self, address: usize, code: &[u8]) -> Option<usize> {
+        let offset = self.rip_relative?;
+        Some((address + self.len).wrapping_add_signed(read_i32(code, offset) as isize))
+    }
+}
+
+fn read_i32(code: &[u8], offset: usize) -> i32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&code[offset..offset + 4]);
+    i32::from_le_bytes(bytes)
+}
+
+/// The architecture limits instructions to 15 bytes.
+pub(crate) const MAX_LEN: usize = 15;
+
+/// Decodes the instruction at the start of `code`.
+///
+/// Returns `None` for invalid encodings or if `code` ends in the middle of the instruction.
+pub(crate) fn decode(code: &[u8]) -> Option<Instruction> {
+    let mut decoder = Decoder { code, offset: 0 };
+    let instruction = decoder.instruction()?;
+
+    (instruction.len <= MAX_LEN).then_some(instruction)
+}
+
+/// Immediate operand sizes.
+#[derive(Clone, Copy)]
+enum Immediate {
+    None,
+    Byte,
+    Word,
+    /// 16 or 32 bits depending on the operand size.
+    Z,
+}
+
+struct Decoder<'a> {
+    code: &'a [u8],
+    offset: usize,
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.code.get(self.offset)?;
+        self.offset += 1;
+        Some(byte)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.code.get(self.offset).copied()
+    }
+
+    fn skip(&mut self, count: usize) -> Option<()> {
+        self.offset += count;
+        (self.offset <= self.code.len()).then_some(())
+    }
+
+    fn instruction(&mut self) -> Option<Instruction> {
+        let mut operand_size = false;
+        let mut address_size = false;
+
+        // Legacy prefixes can come in any order and amount.
+        loop {
+            match self.peek()? {
+                0x66 => operand_size = true,
+                0x67 => address_size = true,
+                0xF0 | 0xF2 | 0xF3 | 0x2E | 0x36 | 0x3E | 0x26 | 0x64 | 0x65 => {}
+                _ => break,
+            }
+            self.offset += 1;
+        }
+
+        // REX has to come right before the opcode.
+        let rex = match self.peek()? {
+            rex @ 0x40..=0x4F => {
+                self.offset += 1;
+                rex
+            }
+            _ => 0,
+        };
+        let rex_w = rex & 0x08 != 0;
+
+        // REX.W wins over the operand size prefix.
+        let z = if operand_size && !rex_w { 2 } else { 4 };
+        let opcode = self.byte()?;
+
+        let mut flow = Flow::Next;
+        let mut relative = None;
+
+        let (modrm, immediate) = match opcode {
+            0x0F => return self.two_byte(operand_size),
+            0xC4 | 0xC5 => return self.vex(opcode),
+            0x62 => return self.evex(),
+
+            0x00..=0x3F => match opcode & 0x07 {
+                0..=3 => (true, Immediate::None),
+                4 => (false, Immediate::Byte),
+                5 => (false, Immediate::Z),
+                // Segment pushes, BCD adjustment and prefixes, all invalid or handled above.
+                _ => return None,
+            },
+            0x50..=0x5F => (false, Immediate::None),
+            0x63 => (true, Immediate::None),
+            0x68 => (false, Immediate::Z),
+            0x69 => (true, Immediate::Z),
+            0x6A => (false, Immediate::Byte),
+            0x6B => (true, Immediate::Byte),
+            0x6C..=0x6F => (false, Immediate::None),
+            0x70..=0x7F => {
+                flow = Flow::Branch {
+                    condition: opcode & 0x0F,
+                };
+                relative = Some((self.offset, 1));
+                (false, Immediate::Byte)
+            }
+            0x80 | 0x83 => (true, Immediate::Byte),
+            0x81 => (true, Immediate::Z),
+            0x84..=0x8F => (true, Immediate::None),
+            0x90..=0x99 | 0x9B..=0x9F => (false, Immediate::None),
+            0xA0..=0xA3 => {
+                // Absolute memory offsets are as wide as addresses.
+                self.skip(if address_size { 4 } else { 8 })?;
+                (false, Immediate::None)
+            }
+            0xA4..=0xA7 | 0xAA..=0xAF => (false, Immediate::None),
+            0xA8 => (false, Immediate::Byte),
+            0xA9 => (false, Immediate::Z),
+            0xB0..=0xB7 => (false, Immediate::Byte),
+            0xB8..=0xBF => {
+                self.skip(if rex_w { 8 } else { z })?;
+                (false, Immediate::None)
+            }
+            0xC0 | 0xC1 | 0xC6 => (true, Immediate::Byte),
+            0xC7 => (true, Immediate::Z),
+            0xC2 | 0xCA => {
+                flow = Flow::Exit;
+                (false, Immediate::Word)
+            }
+            0xC3 | 0xCB | 0xCC | 0xCF => {
+                flow = Flow::Exit;
+                (false, Immediate::None)
+            }
+            0xC8 => {
+                self.skip(2)?;
+                (false, Immediate::Byte)
+            }
+            0xC9 => (false, Immediate::None),
+            0xCD => (false, Immediate::Byte),
+            0xD0..=0xD3 | 0xD8..=0xDF => (true, Immediate::None),
+            0xD7 => (false, Immediate::None),
+            0xE0..=0xE3 => {
+                flow = Flow::Loop;
+                relative = Some((self.offset, 1));
+                (false, Immediate::Byte)
+            }
+            0xE4..=0xE7 => (false, Immediate::Byte),
+            0xE8 | 0xE9 => {
+                // The operand size prefix does not shrink near branches in 64-bit mode.
+                flow = if opcode == 0xE8 {
+                    Flow::Call
+                } else {
+                    Flow::Jump
+                };
+                relative = Some((self.offset, 4));
+                self.skip(4)?;
+                (false, Immediate::None)
+            }
+            0xEB => {
+                flow = Flow::Jump;
+                relative = Some((self.offset, 1));
+                (false, Immediate::Byte)
+            }
+            0xEC..=0xEF | 0xF1 | 0xF5 | 0xF8..=0xFD => (false, Immediate::None),
+            0xF4 => {
+                flow = Flow::Exit;
+                (false, Immediate::None)
+            }
+            0xF6 | 0xF7 => {
+                // Only `test` (/0 and /1) carries an immediate in this group.
+                let reg = (self.peek()? >> 3) & 0x07;
+                let immediate = match (reg, opcode) {
+                    (0 | 1, 0xF6) => Immediate::Byte,
+                    (0 | 1, _) => Immediate::Z,
+                    _ => Immediate::None,
+                };
+                (true, immediate)
+            }
+            0xFE => (true, Immediate::None),
+            0xFF => {
+                // Indirect `jmp` (/4) and far `jmp` (/5) never return.
+                let reg = (self.peek()? >> 3) & 0x07;
+                if reg == 4 || reg == 5 {
+                    flow = Flow::Exit;
+                }
+                (true, Immediate::None)
+            }
+            _ => return None,
+        };
+
+        let rip_relative = match modrm {
+            true => self.modrm()?,
+            false => None,
+        };
+
+        self.immediate(immediate, z)?;
+
+        Some(Instruction {
+            len: self.offset,
+            flow,
+            relative,
+            rip_relative,
+        })
+    }
+
+    fn two_byte(&mut self, operand_size: bool) -> Option<Instruction> {
+        let opcode = self.byte()?;
+        let z = if operand_size { 2 } else { 4 };
+
+        let mut flow = Flow::Next;
+
+        let (modrm, immediate) = match opcode {
+            0x38 => {
+                self.byte()?;
+                (true, Immediate::None)
+            }
+            0x3A => {
+                self.byte()?;
+                (true, Immediate::Byte)
+            }
+            0x04 | 0x0A | 0x0C | 0x24..=0x27 | 0x36 | 0x39 | 0x3B..=0x3F | 0xA6 | 0xA7 => {
+                return None
+            }
+            0x05..=0x09 | 0x0E | 0x30..=0x37 | 0x77 | 0xA0..=0xA2 | 0xA8..=0xAA => {
+                (false, Immediate::None)
+            }
+            0xC8..=0xCF => (false, Immediate::None),
+            0x0B => {
+                flow = Flow::Exit;
+                (false, Immediate::None)
+            }
+            0x80..=0x8F => {
+                let relative = Some((self.offset, 4));
+                self.skip(4)?;
+
+                return Some(Instruction {
+                    len: self.offset,
+                    flow: Flow::Branch {
+                        condition: opcode & 0x0F,
+                    },
+                    relative,
+                    rip_relative: None,
+                });
+            }
+            // Instructions with an immediate byte, 3DNow! (`0F 0F`) keeps its actual opcode there.
+            0x0F | 0x70..=0x73 | 0xA4 | 0xAC | 0xBA | 0xC2 | 0xC4..=0xC6 => (true, Immediate::Byte),
+            _ => (true, Immediate::None),
+        };
+
+        let rip_relative = match modrm {
+            true => self.modrm()?,
+            false => None,
+        };
+
+        self.immediate(immediate, z)?;
+
+        Some(Instruction {
+            len: self.offset,
+            flow,
+            relative: None,
+            rip_relative,
+        })
+    }
+
+    fn vex(&mut self, prefix: u8) -> Option<Instruction> {
+        let map = match prefix {
+            0xC5 => {
+                self.byte()?;
+                1
+            }
+            _ => {
+                let map = self.byte()? & 0x1F;
+                self.byte()?;
+                map
+            }
+        };
+
+        let opcode = self.byte()?;
+
+        // `vzeroupper` and `vzeroall` are the only VEX instructions without a ModRM byte.
+        if map == 1 && opcode == 0x77 {
+            return Some(Instruction {
+                len: self.offset,
+                flow: Flow::Next,
+                relative: None,
+                rip_relative: None,
+            });
+        }
+
+        self.simd(map, opcode)
+    }
+
+    fn evex(&mut self) -> Option<Instruction> {
+        let map = self.byte()? & 0x07;
+        self.skip(2)?;
+        let opcode = self.byte()?;
+
+        self.simd(map, opcode)
+    }
+
+    /// Decodes the rest of a VEX or EVEX instruction after its opcode.
+    fn simd(&mut self, map: u8, opcode: u8) -> Option<Instruction> {
+        let immediate = match (map, opcode) {
+            (3, _) | (1, 0x70..=0x73 | 0xC2 | 0xC4..=0xC6) => Immediate::Byte,
+            (1..=3 | 5 | 6, _) => Immediate::None,
+            _ => return None,
+        };
+
+        let rip_relative = self.modrm()?;
+        self.immediate(immediate, 4)?;
+
+        Some(Instruction {
+            len: self.offset,
+            flow: Flow::Next,
+            relative: None,
+            rip_relative,
+        })
+    }
+
+    /// Skips a ModRM byte with its SIB and displacement, returning the offset of the displacement if
+    /// the operand is RIP-relative.
+    fn modrm(&mut self) -> Option<Option<usize>> {
+        let modrm = self.byte()?;
+        let (mode, rm) = (modrm >> 6, modrm & 0x07);
+
+        // 32-bit addressing in long mode uses the same encoding as 64-bit addressing.
+        match (mode, rm) {
+            (3, _) => Some(None),
+            (0, 5) => {
+                let displacement = self.offset;
+                self.skip(4)?;
+                Some(Some(displacement))
+            }
+            (_, 4) => {
+                let sib = self.byte()?;
+                match mode {
+                    0 if sib & 0x07 == 5 => self.skip(4)?,
+                    1 => self.skip(1)?,
+                    2 => self.skip(4)?,
+                    _ => {}
+                }
+                Some(None)
+            }
+            (1, _) => self.skip(1).map(|_| None),
+            (2, _) => self.skip(4).map(|_| None),
+            _ => Some(None),
+        }
+    }
+
+    fn immediate(&mut self, immediate: Immediate, z: usize) -> Option<()> {
+        match immediate {
+            Immediate::None => Some(()),
+            Immediate::Byte => self.skip(1),
+            Immediate::Word => self.skip(2),
+            Immediate::Z => self.skip(z),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that every entry decodes to a single instruction as long as its bytes, with a
+    /// `[rip + disp32]` displacement at the given offset, and that no prefix of it decodes at all.
+    fn check(cases: &[(&[u8], Option<usize>)]) {
+        for &(code, rip_relative) in cases {
+            let mut padded = code.to_vec();
+            padded.extend([0xCC; 16]);
+
+            let instruction = decode(&padded).unwrap_or_else(|| panic!("{code:02X?}"));
+            assert_eq!(instruction.len, code.len(), "{code:02X?}");
+            assert_eq!(instruction.rip_relative, rip_relative, "{code:02X?}");
+
+            for len in 0..code.len() {
+                assert_eq!(decode(&code[..len]), None, "{:02X?}", &code[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn prefixes() {
+        check(&[
+            (&[0x66, 0x90], None),
+            (&[0xF3, 0x48, 0xAB], None),
+            (&[0x66, 0xB8, 0x34, 0x12], None),
+            (&[0x66, 0x05, 0x34, 0x12], None),
+            (&[0x66, 0x48, 0x05, 0x78, 0x56, 0x34, 0x12], None),
+            (&[0x66, 0x48, 0x81, 0xC0, 0x78, 0x56, 0x34, 0x12], None),
+            (&[0x67, 0x8B, 0x00], None),
+            (&[0xF0, 0x48, 0x0F, 0xB1, 0x0A], None),
+            (
+                &[0x64, 0x48, 0x8B, 0x04, 0x25, 0x28, 0x00, 0x00, 0x00],
+                None,
+            ),
+            (
+                &[0x66, 0x2E, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+                None,
+            ),
+            (&[0x48, 0x63, 0xC7], None),
+            (&[0x41, 0x55], None),
+            (&[0x48, 0x69, 0xC0, 0x10, 0x00, 0x00, 0x00], None),
+            (&[0x6B, 0xC0, 0x10], None),
+        ]);
+    }
+
+    #[test]
+    fn wide_immediates() {
+        check(&[
+            // mov rax, [moffs64], mov [moffs64], al and mov eax, [moffs32]
+            (
+                &[0x48, 0xA1, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11],
+                None,
+            ),
+            (
+                &[0xA2, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11],
+                None,
+            ),
+            (&[0x67, 0xA1, 0x44, 0x33, 0x22, 0x11], None),
+            // mov r64, imm64 and its 32 and 16-bit forms
+            (
+                &[0x48, 0xB8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11],
+                None,
+            ),
+            (&[0xB8, 0x44, 0x33, 0x22, 0x11], None),
+            (&[0x41, 0xBF, 0x44, 0x33, 0x22, 0x11], None),
+            // `test` is the only member of the F6 and F7 groups with an immediate
+            (&[0xF6, 0xC1, 0x01], None),
+            (&[0xF7, 0xC1, 0x44, 0x33, 0x22, 0x11], None),
+            (&[0x66, 0xF7, 0xC1, 0x22, 0x11], None),
+            (&[0x48, 0xF7, 0xD8], None),
+            (&[0xF7, 0xF1], None),
+            (&[0xF6, 0x05, 0x44, 0x33, 0x22, 0x11, 0x01], Some(2)),
+        ]);
+    }
+
+    #[test]
+    fn opcode_maps() {
+        check(&[
+            // 0F 38 and 0F 3A
+            (&[0x66, 0x0F, 0x38, 0x00, 0xC1], None),
+            (&[0x66, 0x0F, 0x3A, 0x0F, 0xC1, 0x08], None),
+            (
+                &[0x66, 0x0F, 0x3A, 0x16, 0x05, 0x44, 0x33, 0x22, 0x11, 0x01],
+                Some(5),
+            ),
+            (&[0x0F, 0x38, 0xF0, 0x06], None),
+            // VEX, `vzeroupper` and `vzeroall` have no ModRM byte
+            (&[0xC5, 0xF8, 0x77], None),
+            (&[0xC5, 0xFC, 0x77], None),
+            (&[0xC5, 0xFD, 0x6F, 0x05, 0x44, 0x33, 0x22, 0x11], Some(4)),
+            (
+                &[0xC4, 0xE2, 0x7D, 0x18, 0x05, 0x44, 0x33, 0x22, 0x11],
+                Some(5),
+            ),
+            (&[0xC4, 0xE3, 0x7D, 0x18, 0xC1, 0x01], None),
+            (&[0xC5, 0xF9, 0x70, 0xC1, 0x1B], None),
+            // EVEX
+            (
+                &[0x62, 0xF1, 0x7C, 0x48, 0x10, 0x05, 0x44, 0x33, 0x22, 0x11],
+                Some(6),
+            ),
+            (&[0x62, 0xF1, 0x7C, 0x48, 0x10, 0x40, 0x01], None),
+            (&[0x62, 0xF3, 0x7D, 0x48, 0x1B, 0xC1, 0x01], None),
+        ]);
+    }
+
+    #[test]
+    fn memory_operands() {
+        check(&[
+            (&[0x8B, 0x00], None),
+            (&[0x8B, 0x04, 0x24], None),
+            (&[0x8B, 0x04, 0x25, 0x44, 0x33, 0x22, 0x11], None),
+            (&[0x8B, 0x05, 0x44, 0x33, 0x22, 0x11], Some(2)),
+            (&[0x8B, 0x40, 0x08], None),
+            (&[0x8B, 0x44, 0x24, 0x08], None),
+            (&[0x8B, 0x80, 0x44, 0x33, 0x22, 0x11], None),
+            (&[0x8B, 0x84, 0x24, 0x44, 0x33, 0x22, 0x11], None),
+            (&[0x8B, 0x45, 0x00], None),
+            (&[0x8B, 0x04, 0x05, 0x44, 0x33, 0x22, 0x11], None),
+            (&[0x8B, 0xC1], None),
+            (
+                &[0xC7, 0x05, 0x44, 0x33, 0x22, 0x11, 0x01, 0x00, 0x00, 0x00],
+                Some(2),
+            ),
+            (&[0x48, 0x83, 0x3D, 0x44, 0x33, 0x22, 0x11, 0x00], Some(3)),
+            (&[0x81, 0x7C, 0x24, 0x08, 0x44, 0x33, 0x22, 0x11], None),
+        ]);
+    }
+
+    #[test]
+    fn rip_target() {
+        // mov eax, [rip - 0x10] and cmp qword [rip + 0x20], 0
+        let code = [0x8B, 0x05, 0xF0, 0xFF, 0xFF, 0xFF];
+        let instruction = decode(&code).unwrap();
+        assert_eq!(instruction.rip_target(0x1000, &code), Some(0xFF6));
+
+        let code = [0x48, 0x83, 0x3D, 0x20, 0x00, 0x00, 0x00, 0x00];
+        let instruction = decode(&code).unwrap();
+        assert_eq!(instruction.rip_target(0x1000, &code), Some(0x1028));
+    }
+
+    /// Checks how a single instruction affects the flow of execution.
+    fn check_flow(code: &[u8], flow: Flow, relative: Option<(usize, usize)>) {
+        let instruction = decode(code).unwrap_or_else(|| panic!("{code:02X?}"));
+        assert_eq!(instruction.len, code.len(), "{code:02X?}");
+        assert_eq!(instruction.flow, flow, "{code:02X?}");
+        assert_eq!(instruction.relative, relative, "{code:02X?}");
+    }
+
+    #[test]
+    fn flow() {
+        check_flow(&[0xC3], Flow::Exit, None);
+        check_flow(&[0xC2, 0x08, 0x00], Flow::Exit, None);
+        check_flow(&[0xCC], Flow::Exit, None);
+        check_flow(&[0x0F, 0x0B], Flow::Exit, None);
+        check_flow(&[0xFF, 0xE0], Flow::Exit, None);
+        check_flow(&[0xFF, 0x25, 0x44, 0x33, 0x22, 0x11], Flow::Exit, None);
+        check_flow(&[0xFF, 0x15, 0x44, 0x33, 0x22, 0x11], Flow::Next, None);
+        check_flow(&[0xE8, 0xFB, 0xFF, 0xFF, 0xFF], Flow::Call, Some((1, 4)));
+        check_flow(&[0xE9, 0xFB, 0xFF, 0xFF, 0xFF], Flow::Jump, Some((1, 4)));
+        check_flow(&[0xEB, 0xFE], Flow::Jump, Some((1, 1)));
+        check_flow(&[0x74, 0x05], Flow::Branch { condition: 4 }, Some((1, 1)));
+        check_flow(
+            &[0x0F, 0x8F, 0x44, 0x33, 0x22, 0x11],
+            Flow::Branch { condition: 0xF },
+            Some((2, 4)),
+        );
+        check_flow(&[0xE2, 0xFE], Flow::Loop, Some((1, 1)));
+        check_flow(&[0x67, 0xE3, 0xFE], Flow::Loop, Some((2, 1)));
+
+        // jmp -5 and jne +5 from 0x1000
+        let code = [0xE9, 0xFB, 0xFF, 0xFF, 0xFF];
+        assert_eq!(
+            decode(&code).unwrap().branch_target(0x1000, &code),
+            Some(0x1000)
+        );
+        let code = [0x75, 0x05];
+        assert_eq!(
+            decode(&code).unwrap().branch_target(0x1000, &code),
+            Some(0x1007)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let cases: [&[u8]; 9] = [
+            // push es, far jmp and salc do not exist in 64-bit mode
+            &[0x06],
+            &[0xEA, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            &[0xD6],
+            // undefined two byte opcodes
+            &[0x0F, 0x04],
+            &[0x0F, 0x0A],
+            // VEX and EVEX with a reserved opcode map
+            &[0xC4, 0xE0, 0x7D, 0x00, 0xC0],
+            &[0x62, 0xF4, 0x7C, 0x48, 0x10, 0xC0],
+            // only prefixes
+            &[0x66, 0xF3, 0x48],
+            // longer than 15 bytes
+            &[
+                0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+                0x66, 0x90,
+            ],
+        ];
+
+        for code in cases {
+            assert_eq!(decode(code), None, "{code:02X?}");
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Swapping a single function pointer in a table, shared by the hooks that do not touch code.
+
+use super::{write, HookError};
+
+/// A function pointer at `address` replaced by another one until this is dropped.
+pub(crate) struct Entry {
+    pub(crate) address: usize,
+    pub(crate) original: usize,
+    pub(crate) replacement: usize,
+}
+
+impl Entry {
+    /// Stores `replacement` at `address`, changing the protection of the page while it is written.
+    ///
+    /// # Safety
+    /// `address` must point to an aligned function pointer that may be replaced.
+    pub(crate) unsafe fn replace(
+        address: *mut usize,
+        replacement: usize,
+    ) -> Result<Self, HookError> {
+        let original = unsafe { address.read() };
+
+        unsafe { write(address.cast(), &replacement.to_ne_bytes())? };
+
+        Ok(Self {
+            address: address as usize,
+            original,
+            replacement,
+        })
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        // A hook installed on top of this one owns the pointer now.
+        let address = self.address as *mut usize;
+        if unsafe { address.read() } == self.replacement {
+            let _ = unsafe { write(address.cast(), &self.original.to_ne_bytes()) };
+        }
+    }
+}
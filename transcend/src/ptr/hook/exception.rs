@@ -0,0 +1,767 @@
+//! Hooks that are reached through exceptions instead of jumps, for code that is checked for
+//! modifications.
+//!
+//! A breakpoint replaces a single byte with `int3`, a page hook changes no code at all and makes
+//! the page holding the instruction inaccessible instead. Windows delivers the exceptions to a
+//! vectored exception handler, Linux raises `SIGTRAP` and `SIGSEGV`.
+//!
+//! Hardware breakpoints in the debug registers `DR0` to `DR3` are not supported. They would leave
+//! the code untouched as well, but there are only four, every thread has its own, and threads
+//! started later would not have them set.
+
+use core::fmt;
+use std::cell::Cell;
+use std::ptr::{copy_nonoverlapping, null_mut};
+use std::slice::from_raw_parts;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+#[cfg(target_os = "windows")]
+use windows::Win32::{
+    Foundation::{EXCEPTION_BREAKPOINT, EXCEPTION_SINGLE_STEP, STATUS_GUARD_PAGE_VIOLATION},
+    System::{
+        Diagnostics::Debug::{
+            AddVectoredExceptionHandler, CONTEXT, EXCEPTION_POINTERS, EXCEPTION_RECORD,
+        },
+        Memory::PAGE_GUARD,
+        SystemInformation::{GetSystemInfo, SYSTEM_INFO},
+    },
+};
+
+#[cfg(target_os = "linux")]
+use {
+    super::super::elf::page_size,
+    libc::{
+        c_int, c_void, raise, sigaction, sigemptyset, siginfo_t, ucontext_t, PROT_NONE, REG_EFL,
+        REG_R10, REG_R11, REG_R12, REG_R13, REG_R14, REG_R15, REG_R8, REG_R9, REG_RAX, REG_RBP,
+        REG_RBX, REG_RCX, REG_RDI, REG_RDX, REG_RIP, REG_RSI, REG_RSP, SA_ONSTACK, SA_SIGINFO,
+        SIGSEGV, SIGTRAP, SIG_DFL, SIG_IGN,
+    },
+    std::sync::atomic::AtomicI32,
+};
+
+use super::{allocator, relocate_back, stolen_len, write, Context, HookError};
+use crate::ptr::protect::{self, regions, Native};
+use crate::ptr::Address;
+use crate::Error;
+
+/// How many exception hooks can be installed at once.
+const CAPACITY: usize = 64;
+
+const INT3: u8 = 0xCC;
+
+/// The trap flag in `rflags`, which raises a single-step exception after the next instruction.
+const TRAP_FLAG: u64 = 0x100;
+
+/// The `si_code` of a `SIGTRAP` raised by `int3`.
+#[cfg(target_os = "linux")]
+const SI_KERNEL: c_int = 0x80;
+
+#[cfg(target_os = "windows")]
+const EXCEPTION_CONTINUE_EXECUTION: i32 = -1;
+#[cfg(target_os = "windows")]
+const EXCEPTION_CONTINUE_SEARCH: i32 = 0;
+
+type Callback = dyn Fn(&mut Context) + Send + Sync;
+
+/// The installed hooks, which the handler reads without taking a lock.
+static HOOKS: [AtomicPtr<Inner>; CAPACITY] = [const { AtomicPtr::new(null_mut()) }; CAPACITY];
+
+/// The number of threads in the handler, which may still use a hook that was just removed.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes installing and removing hooks.
+static INSTALL: Mutex<()> = Mutex::new(());
+
+/// The pages of the last removed page hooks, with [`REMOVING`] set until they are accessible
+/// again. Threads that faulted on one before its hook was removed may still be on their way to the
+/// handler.
+static UNHOOKED: [AtomicUsize; CAPACITY] = [const { AtomicUsize::new(0) }; CAPACITY];
+
+/// The next entry of [`UNHOOKED`] to reuse.
+static UNHOOKED_NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks a page in [`UNHOOKED`] that may still be inaccessible, page addresses leave it clear.
+const REMOVING: usize = 1;
+
+thread_local! {
+    /// The page a thread makes inaccessible again once it stepped over an instruction that
+    /// touched it.
+    static STEPPING: Cell<usize> = const { Cell::new(0) };
+
+    /// The instruction a thread last retried after a fault on an unhooked page.
+    static RETRIED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A page that is inaccessible while a page hook is installed.
+#[derive(Clone, Copy)]
+struct Page {
+    start: usize,
+    len: usize,
+    protection: Native,
+}
+
+impl Page {
+    fn contains(&self, address: usize) -> bool {
+        (self.start..self.start + self.len).contains(&address)
+    }
+}
+
+struct Inner {
+    address: usize,
+    /// The page of a page hook, breakpoints have none.
+    page: Option<Page>,
+    /// The instruction at `address` followed by a jump back.
+    trampoline: usize,
+    callback: Box<Callback>,
+}
+
+/// A callback that runs whenever execution reaches an instruction, like a
+/// [`MidHook`](super::MidHook), but reached through an exception instead of a jump.
+///
+/// The hook is removed when it is dropped.
+///
+/// # Examples
+/// ```no_run
+/// use transcend::ptr::{base, breakpoint_hook, Rva};
+///
+/// # fn main() -> Result<(), transcend::Error> {
+/// unsafe {
+///     // The integrity check only hashes whole functions, a single `int3` goes unnoticed.
+///     let hook = breakpoint_hook(base()? + Rva::new(0x2843C7), |context| {
+///         context.rcx = 0;
+///     })?;
+///
+///     std::mem::forget(hook);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ExceptionHook {
+    inner: *mut Inner,
+    index: usize,
+    /// The byte `int3` replaced, for breakpoints.
+    original: Option<u8>,
+}
+
+// SAFETY: The callback is `Send + Sync` and everything else is plain addresses.
+unsafe impl Send for ExceptionHook {}
+unsafe impl Sync for ExceptionHook {}
+
+/// Runs `callback` every time execution reaches `address`, by replacing the first byte of the
+/// instruction with `int3`.
+///
+/// The instruction itself runs from a trampoline afterwards. On Linux the callback runs inside a
+/// signal handler, so it must not take locks the interrupted thread may hold.
+///
+/// # Safety
+/// `address` must point to the start of an instruction and no thread may be executing it while the
+/// hook is installed.
+pub unsafe fn breakpoint_hook(
+    address: Address,
+    callback: impl Fn(&mut Context) + Send + Sync + 'static,
+) -> Result<ExceptionHook, Error> {
+    let original = unsafe { address.read::<u8>() };
+    let mut hook = unsafe { register(address.as_usize(), None, Box::new(callback))? };
+
+    unsafe { write(address.as_mut_ptr(), &[INT3])? };
+    hook.original = Some(original);
+
+    Ok(hook)
+}
+
+/// Runs `callback` every time execution reaches `address` without modifying any code, by making
+/// the page that holds it inaccessible.
+///
+/// Every other access to the page faults as well and is let through by stepping over the
+/// instruction with the page accessible, which is slow and lets other threads pass unnoticed in
+/// the meantime. The page must not hold code the handler itself runs. On Linux the callback runs
+/// inside a signal handler, so it must not take locks the interrupted thread may hold.
+///
+/// # Safety
+/// `address` must point to the start of an instruction and nothing may change the protection of
+/// its page while the hook is installed.
+pub unsafe fn page_hook(
+    address: Address,
+    callback: impl Fn(&mut Context) + Send + Sync + 'static,
+) -> Result<ExceptionHook, Error> {
+    let address = address.as_usize();
+    let len = page_len();
+    let start = address & !(len - 1);
+
+    // Another hook may have made the page inaccessible already.
+    let protection = {
+        let _install = INSTALL.lock().unwrap_or_else(PoisonError::into_inner);
+        match hooked_page(|page| page.start == start) {
+            Some(page) => Some(page.protection),
+            None => regions(start, 1).ok().map(|regions| regions[0].protection),
+        }
+    }
+    .ok_or(HookError::Protect)?;
+
+    let page = Page {
+        start,
+        len,
+        protection,
+    };
+
+    let hook = unsafe { register(address, Some(page), Box::new(callback))? };
+
+    if !unsafe { protect(page) } {
+        return Err(HookError::Protect.into());
+    }
+
+    Ok(hook)
+}
+
+/// Builds the trampoline and makes the hook visible to the handler.
+unsafe fn register(
+    address: usize,
+    page: Option<Page>,
+    callback: Box<Callback>,
+) -> Result<ExceptionHook, HookError> {
+    install()?;
+
+    let len = unsafe { stolen_len(address as *const u8, 1)? };
+    let original = unsafe { from_raw_parts(address as *const u8, len) };
+
+    let trampoline = allocator::allocate(address).ok_or(HookError::Allocate)?;
+    let code = match relocate_back(original, address, trampoline) {
+        Ok(relocated) if relocated.code.len() <= allocator::SLOT_LEN => relocated.code,
+        result => {
+            unsafe { allocator::free(trampoline) };
+            return Err(result.err().unwrap_or(HookError::Allocate));
+        }
+    };
+
+    unsafe { copy_nonoverlapping(code.as_ptr(), trampoline as *mut u8, code.len()) };
+
+    let inner = Box::into_raw(Box::new(Inner {
+        address,
+        page,
+        trampoline,
+        callback,
+    }));
+
+    let _install = INSTALL.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let index = HOOKS.iter().position(|hook| {
+        hook.compare_exchange(null_mut(), inner, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    });
+
+    match index {
+        Some(index) => Ok(ExceptionHook {
+            inner,
+            index,
+            original: None,
+        }),
+        None => {
+            drop(unsafe { Box::from_raw(inner) });
+            unsafe { allocator::free(trampoline) };
+            Err(HookError::Allocate)
+        }
+    }
+}
+
+impl Drop for ExceptionHook {
+    fn drop(&mut self) {
+        let _install = INSTALL.lock().unwrap_or_else(PoisonError::into_inner);
+        let inner = unsafe { &*self.inner };
+
+        if let Some(original) = self.original {
+            // The trampoline has to stay around if the code still traps.
+            if unsafe { write(inner.address as *mut u8, &[original]) }.is_err() {
+                return;
+            }
+        }
+
+        let unhooked = inner.page.map(|page| {
+            let index = UNHOOKED_NEXT.fetch_add(1, Ordering::SeqCst) % CAPACITY;
+            UNHOOKED[index].store(page.start | REMOVING, Ordering::SeqCst);
+            index
+        });
+
+        HOOKS[self.index].store(null_mut(), Ordering::SeqCst);
+
+        if let Some(page) = inner.page {
+            if hooked_page(|other| other.start == page.start).is_none() {
+                unsafe { restore(page) };
+            }
+        }
+
+        // Threads already in the handler may still be using the hook.
+        while ACTIVE.load(Ordering::SeqCst) != 0 {
+            std::thread::yield_now();
+        }
+
+        if let (Some(page), Some(index)) = (inner.page, unhooked) {
+            // A thread that finished stepping over an instruction in the meantime may have made the
+            // page inaccessible again.
+            if hooked_page(|other| other.start == page.start).is_none() {
+                unsafe { restore(page) };
+            }
+
+            UNHOOKED[index].store(page.start, Ordering::SeqCst);
+        }
+
+        unsafe { allocator::free(inner.trampoline) };
+        drop(unsafe { Box::from_raw(self.inner) });
+    }
+}
+
+impl fmt::Debug for ExceptionHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = unsafe { &*self.inner };
+
+        f.debug_struct("ExceptionHook")
+            .field("address", &(inner.address as *const u8))
+            .field("page", &inner.page.map(|page| page.start as *const u8))
+            .finish()
+    }
+}
+
+/// Returns the first installed hook that matches `predicate`.
+///
+/// The hook stays valid while the caller is counted in [`ACTIVE`] or holds [`INSTALL`].
+fn find(predicate: impl Fn(&Inner) -> bool) -> Option<&'static Inner> {
+    HOOKS
+        .iter()
+        .map(|hook| hook.load(Ordering::SeqCst))
+        .filter(|hook| !hook.is_null())
+        .map(|hook| unsafe { &*hook })
+        .find(|hook| predicate(hook))
+}
+
+/// Returns the page of the first installed page hook that matches `predicate`.
+fn hooked_page(predicate: impl Fn(&Page) -> bool) -> Option<Page> {
+    find(|inner| inner.page.as_ref().is_some_and(&predicate)).and_then(|inner| inner.page)
+}
+
+/// What the handler does with an exception.
+enum Handled {
+    /// It was not caused by a hook.
+    No,
+    /// Continue at the instruction that raised it, which starts at the address.
+    Retry(usize),
+    /// Continue at the trampoline of the hook after running its callback.
+    Hook(&'static Inner),
+    /// Step over the faulting instruction with `page` accessible.
+    Step(Page),
+    /// The step over an instruction is done.
+    Stepped,
+}
+
+/// Decides what to do about a breakpoint at `address`.
+fn breakpoint(address: usize) -> Handled {
+    if let Some(inner) = find(|inner| inner.page.is_none() && inner.address == address) {
+        return Handled::Hook(inner);
+    }
+
+    // The hook was removed while the thread was on its way to the handler.
+    match unsafe { *(address as *const u8) } {
+        INT3 => Handled::No,
+        _ => Handled::Retry(address),
+    }
+}
+
+/// Decides what to do about an access to `address` by the instruction at `rip`.
+fn fault(address: usize, rip: usize) -> Handled {
+    if let Some(inner) = find(|inner| inner.page.is_some() && inner.address == rip) {
+        RETRIED.set(0);
+        return Handled::Hook(inner);
+    }
+
+    if let Some(page) = hooked_page(|page| page.contains(address)) {
+        RETRIED.set(0);
+        return Handled::Step(page);
+    }
+
+    // The hook was removed while the thread was on its way to the handler. Until the page is
+    // accessible again the thread keeps retrying, after that a second fault is a real one.
+    let start = address & !(page_len() - 1);
+    let unhooked = UNHOOKED
+        .iter()
+        .map(|page| page.load(Ordering::SeqCst))
+        .filter(|&page| page != 0 && page & !REMOVING == start)
+        .max();
+
+    match unhooked {
+        Some(page) if page & REMOVING != 0 => Handled::Retry(rip),
+        Some(_) if RETRIED.replace(rip) != rip => Handled::Retry(rip),
+        _ => Handled::No,
+    }
+}
+
+/// Makes the page of a finished step inaccessible again, if it is still hooked.
+fn stepped() -> Handled {
+    let start = STEPPING.replace(0);
+    if start == 0 {
+        return Handled::No;
+    }
+
+    if let Some(page) = hooked_page(|page| page.start == start) {
+        unsafe { protect(page) };
+    }
+
+    Handled::Stepped
+}
+
+/// Installs the exception handler once.
+fn install() -> Result<(), HookError> {
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+    match INSTALLED.get_or_init(|| unsafe { install_handler() }) {
+        true => Ok(()),
+        false => Err(HookError::Handler),
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn install_handler() -> bool {
+    !unsafe { AddVectoredExceptionHandler(1, Some(handle)) }.is_null()
+}
+
+#[cfg(target_os = "windows")]
+unsafe extern "system" fn handle(exception: *mut EXCEPTION_POINTERS) -> i32 {
+    let exception = unsafe { &*exception };
+    let record = unsafe { &*exception.ExceptionRecord };
+    let context = unsafe { &mut *exception.ContextRecord };
+
+    ACTIVE.fetch_add(1, Ordering::SeqCst);
+    let handled = unsafe { dispatch(record, context) };
+    ACTIVE.fetch_sub(1, Ordering::SeqCst);
+
+    match handled {
+        true => EXCEPTION_CONTINUE_EXECUTION,
+        false => EXCEPTION_CONTINUE_SEARCH,
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn dispatch(record: &EXCEPTION_RECORD, context: &mut CONTEXT) -> bool {
+    let address = record.ExceptionAddress as usize;
+
+    let handled = match record.ExceptionCode {
+        EXCEPTION_BREAKPOINT => breakpoint(address),
+        EXCEPTION_SINGLE_STEP => stepped(),
+        // The guard is lifted by the exception itself.
+        STATUS_GUARD_PAGE_VIOLATION => match fault(record.ExceptionInformation[1], address) {
+            Handled::Hook(inner) => {
+                if let Some(page) = inner.page {
+                    unsafe { protect(page) };
+                }
+                Handled::Hook(inner)
+            }
+            handled => handled,
+        },
+        _ => Handled::No,
+    };
+
+    match handled {
+        Handled::No => return false,
+        Handled::Retry(at) => context.Rip = at as u64,
+        Handled::Hook(inner) => {
+            let mut registers = load(context);
+            (inner.callback)(&mut registers);
+            store(&registers, context);
+            context.Rip = inner.trampoline as u64;
+        }
+        Handled::Step(page) => {
+            STEPPING.set(page.start);
+            context.EFlags |= TRAP_FLAG as u32;
+        }
+        Handled::Stepped => context.EFlags &= !(TRAP_FLAG as u32),
+    }
+
+    true
+}
+
+#[cfg(target_os = "windows")]
+fn load(context: &CONTEXT) -> Context {
+    let xmm = unsafe { context.Anonymous.FltSave.XmmRegisters };
+
+    Context {
+        xmm: xmm.map(|register| (register.High as u64 as u128) << 64 | register.Low as u128),
+        rax: context.Rax,
+        rcx: context.Rcx,
+        rdx: context.Rdx,
+        rbx: context.Rbx,
+        rsp: context.Rsp,
+        rbp: context.Rbp,
+        rsi: context.Rsi,
+        rdi: context.Rdi,
+        r8: context.R8,
+        r9: context.R9,
+        r10: context.R10,
+        r11: context.R11,
+        r12: context.R12,
+        r13: context.R13,
+        r14: context.R14,
+        r15: context.R15,
+        rflags: context.EFlags as u64,
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn store(registers: &Context, context: &mut CONTEXT) {
+    let xmm = unsafe { &mut context.Anonymous.FltSave.XmmRegisters };
+    for (register, &value) in xmm.iter_mut().zip(&registers.xmm) {
+        register.Low = value as u64;
+        register.High = (value >> 64) as i64;
+    }
+
+    context.Rax = registers.rax;
+    context.Rcx = registers.rcx;
+    context.Rdx = registers.rdx;
+    context.Rbx = registers.rbx;
+    context.Rbp = registers.rbp;
+    context.Rsi = registers.rsi;
+    context.Rdi = registers.rdi;
+    context.R8 = registers.r8;
+    context.R9 = registers.r9;
+    context.R10 = registers.r10;
+    context.R11 = registers.r11;
+    context.R12 = registers.r12;
+    context.R13 = registers.r13;
+    context.R14 = registers.r14;
+    context.R15 = registers.r15;
+    context.EFlags = registers.rflags as u32;
+}
+
+#[cfg(target_os = "windows")]
+fn page_len() -> usize {
+    let mut info = SYSTEM_INFO::default();
+    unsafe { GetSystemInfo(&mut info) };
+    info.dwPageSize as usize
+}
+
+/// The handler that was installed before ours, for signals that are not caused by a hook.
+#[cfg(target_os = "linux")]
+struct Previous {
+    handler: AtomicUsize,
+    flags: AtomicI32,
+}
+
+#[cfg(target_os = "linux")]
+static PREVIOUS_TRAP: Previous = Previous {
+    handler: AtomicUsize::new(SIG_DFL),
+    flags: AtomicI32::new(0),
+};
+
+#[cfg(target_os = "linux")]
+static PREVIOUS_SEGV: Previous = Previous {
+    handler: AtomicUsize::new(SIG_DFL),
+    flags: AtomicI32::new(0),
+};
+
+#[cfg(target_os = "linux")]
+unsafe fn install_handler() -> bool {
+    [(SIGTRAP, &PREVIOUS_TRAP), (SIGSEGV, &PREVIOUS_SEGV)]
+        .into_iter()
+        .all(|(signal, previous)| {
+            let mut action: sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = handle as *const () as usize;
+            // The alternate stack lets a stack overflow still reach the handler of the runtime.
+            action.sa_flags = SA_SIGINFO | SA_ONSTACK;
+            unsafe { sigemptyset(&mut action.sa_mask) };
+
+            let mut old: sigaction = unsafe { std::mem::zeroed() };
+            if unsafe { sigaction(signal, &action, &mut old) } != 0 {
+                return false;
+            }
+
+            previous.handler.store(old.sa_sigaction, Ordering::Release);
+            previous.flags.store(old.sa_flags, Ordering::Release);
+            true
+        })
+}
+
+#[cfg(target_os = "linux")]
+extern "C" fn handle(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    ACTIVE.fetch_add(1, Ordering::SeqCst);
+    let handled = unsafe { dispatch(signal, &*info, &mut *context.cast::<ucontext_t>()) };
+    ACTIVE.fetch_sub(1, Ordering::SeqCst);
+
+    if !handled {
+        unsafe { chain(signal, info, context) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn dispatch(signal: c_int, info: &siginfo_t, context: &mut ucontext_t) -> bool {
+    let rip = context.uc_mcontext.gregs[REG_RIP as usize] as usize;
+
+    let handled = match signal {
+        SIGTRAP => match stepped() {
+            // `int3` leaves the instruction pointer after itself.
+            Handled::No if info.si_code == SI_KERNEL => breakpoint(rip - 1),
+            handled => handled,
+        },
+        SIGSEGV => fault(unsafe { info.si_addr() } as usize, rip),
+        _ => Handled::No,
+    };
+
+    let gregs = &mut context.uc_mcontext.gregs;
+    match handled {
+        Handled::No => return false,
+        Handled::Retry(at) => gregs[REG_RIP as usize] = at as i64,
+        Handled::Hook(inner) => {
+            let mut registers = load(context);
+            (inner.callback)(&mut registers);
+            store(&registers, context);
+            context.uc_mcontext.gregs[REG_RIP as usize] = inner.trampoline as i64;
+        }
+        Handled::Step(page) => {
+            unsafe { restore(page) };
+            STEPPING.set(page.start);
+            gregs[REG_EFL as usize] |= TRAP_FLAG as i64;
+        }
+        Handled::Stepped => gregs[REG_EFL as usize] &= !(TRAP_FLAG as i64),
+    }
+
+    true
+}
+
+/// Passes a signal that was not caused by a hook on to the handler installed before.
+#[cfg(target_os = "linux")]
+unsafe fn chain(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    let previous = match signal {
+        SIGTRAP => &PREVIOUS_TRAP,
+        _ => &PREVIOUS_SEGV,
+    };
+
+    match previous.handler.load(Ordering::Acquire) {
+        // Let the default action end the process once the handler returned.
+        SIG_DFL | SIG_IGN => {
+            let mut action: sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = SIG_DFL;
+            unsafe { sigaction(signal, &action, null_mut()) };
+            unsafe { raise(signal) };
+        }
+        handler if previous.flags.load(Ordering::Acquire) & SA_SIGINFO != 0 => {
+            let handler: extern "C" fn(c_int, *mut siginfo_t, *mut c_void) =
+                unsafe { std::mem::transmute(handler) };
+            handler(signal, info, context);
+        }
+        handler => {
+            let handler: extern "C" fn(c_int) = unsafe { std::mem::transmute(handler) };
+            handler(signal);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn load(context: &ucontext_t) -> Context {
+    let register = |index: c_int| context.uc_mcontext.gregs[index as usize] as u64;
+
+    let mut xmm = [0; 16];
+    if let Some(state) = unsafe { context.uc_mcontext.fpregs.as_ref() } {
+        for (value, register) in xmm.iter_mut().zip(&state._xmm) {
+            *value = register
+                .element
+                .iter()
+                .rev()
+                .fold(0, |value, &element| value << 32 | element as u128);
+        }
+    }
+
+    Context {
+        xmm,
+        rax: register(REG_RAX),
+        rcx: register(REG_RCX),
+        rdx: register(REG_RDX),
+        rbx: register(REG_RBX),
+        rsp: register(REG_RSP),
+        rbp: register(REG_RBP),
+        rsi: register(REG_RSI),
+        rdi: register(REG_RDI),
+        r8: register(REG_R8),
+        r9: register(REG_R9),
+        r10: register(REG_R10),
+        r11: register(REG_R11),
+        r12: register(REG_R12),
+        r13: register(REG_R13),
+        r14: register(REG_R14),
+        r15: register(REG_R15),
+        rflags: register(REG_EFL),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn store(registers: &Context, context: &mut ucontext_t) {
+    if let Some(state) = unsafe { context.uc_mcontext.fpregs.as_mut() } {
+        for (register, &value) in state._xmm.iter_mut().zip(&registers.xmm) {
+            for (index, element) in register.element.iter_mut().enumerate() {
+                *element = (value >> (32 * index)) as u32;
+            }
+        }
+    }
+
+    let gregs = &mut context.uc_mcontext.gregs;
+    for (index, value) in [
+        (REG_RAX, registers.rax),
+        (REG_RCX, registers.rcx),
+        (REG_RDX, registers.rdx),
+        (REG_RBX, registers.rbx),
+        (REG_RBP, registers.rbp),
+        (REG_RSI, registers.rsi),
+        (REG_RDI, registers.rdi),
+        (REG_R8, registers.r8),
+        (REG_R9, registers.r9),
+        (REG_R10, registers.r10),
+        (REG_R11, registers.r11),
+        (REG_R12, registers.r12),
+        (REG_R13, registers.r13),
+        (REG_R14, registers.r14),
+        (REG_R15, registers.r15),
+        (REG_EFL, registers.rflags),
+    ] {
+        gregs[index as usize] = value as i64;
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn page_len() -> usize {
+    page_size()
+}
+
+/// Makes `page` raise an exception on the next access.
+unsafe fn protect(page: Page) -> bool {
+    #[cfg(target_os = "windows")]
+    let protection = page.protection | PAGE_GUARD;
+    #[cfg(target_os = "linux")]
+    let protection = PROT_NONE;
+
+    unsafe { protect::protect(page.start, page.len, protection) }.is_ok()
+}
+
+unsafe fn restore(page: Page) {
+    let _ = unsafe { protect::protect(page.start, page.len, page.protection) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unhooked_page() {
+        let start = 0x7A5C_0000_0000;
+        let (address, rip) = (start + 0x10, start + 0x20);
+        assert!(matches!(fault(address, rip), Handled::No));
+
+        // The page may still be inaccessible while its hook is removed.
+        UNHOOKED[CAPACITY - 1].store(start | REMOVING, Ordering::SeqCst);
+        for _ in 0..3 {
+            assert!(matches!(fault(address, rip), Handled::Retry(at) if at == rip));
+        }
+
+        // Once it is accessible again only the first fault can be a late one.
+        UNHOOKED[CAPACITY - 1].store(start, Ordering::SeqCst);
+        assert!(matches!(fault(address, rip), Handled::Retry(at) if at == rip));
+        assert!(matches!(fault(address, rip), Handled::No));
+
+        UNHOOKED[CAPACITY - 1].store(0, Ordering::SeqCst);
+        assert!(matches!(fault(address + 0x10, rip), Handled::No));
+    }
+}
@@ -0,0 +1,250 @@
+//! Hooks that replace entries of the global offset table of an ELF module.
+
+use core::fmt;
+use std::ffi::{c_void, CStr, CString};
+use std::marker::PhantomData;
+use std::mem::transmute_copy;
+use std::slice::from_raw_parts;
+
+use libc::{
+    c_int, dl_iterate_phdr, dl_phdr_info, dlsym, getauxval, size_t, Elf64_Phdr, AT_SYSINFO_EHDR,
+    PT_DYNAMIC, RTLD_DEFAULT,
+};
+
+use super::entry::Entry;
+use super::HookError;
+use crate::ptr::elf::{image_end, image_start};
+use crate::ptr::{FnPtr, Module};
+use crate::Error;
+
+const DT_NULL: i64 = 0;
+const DT_PLTRELSZ: i64 = 2;
+const DT_STRTAB: i64 = 5;
+const DT_SYMTAB: i64 = 6;
+const DT_RELA: i64 = 7;
+const DT_RELASZ: i64 = 8;
+const DT_JMPREL: i64 = 23;
+
+const R_X86_64_GLOB_DAT: u32 = 6;
+const R_X86_64_JUMP_SLOT: u32 = 7;
+
+/// The size of an `Elf64_Sym`, whose name is the first field.
+const SYMBOL_LEN: usize = 24;
+
+#[repr(C)]
+struct Dyn {
+    tag: i64,
+    value: u64,
+}
+
+#[repr(C)]
+struct Rela {
+    offset: u64,
+    info: u64,
+    addend: i64,
+}
+
+/// A function imported by a module whose entries in the global offset table point to a
+/// replacement.
+///
+/// Only calls made by that module are redirected, and the entries are restored when the hook is
+/// dropped.
+///
+/// # Examples
+/// ```no_run
+/// use std::ffi::c_void;
+/// use std::sync::OnceLock;
+/// use transcend::ptr::{got_hook, module, GotHook};
+///
+/// type Malloc = unsafe extern "C" fn(usize) -> *mut c_void;
+///
+/// static MALLOC: OnceLock<GotHook<Malloc>> = OnceLock::new();
+///
+/// unsafe extern "C" fn malloc(size: usize) -> *mut c_void {
+///     // Leave room for the fields the mod adds to every allocation.
+///     unsafe { (MALLOC.get().unwrap().original())(size + 64) }
+/// }
+///
+/// unsafe {
+///     let engine = module("libengine.so").unwrap();
+///     MALLOC.get_or_init(|| got_hook(&engine, "malloc", malloc as Malloc).unwrap());
+/// }
+/// ```
+pub struct GotHook<F: FnPtr> {
+    /// A function can have an entry for calls through the PLT and one for taking its address.
+    entries: Vec<Entry>,
+    original: usize,
+    function: PhantomData<F>,
+}
+
+impl<F: FnPtr> GotHook<F> {
+    /// Returns the function the entries pointed to before.
+    #[must_use]
+    pub fn original(&self) -> F {
+        // SAFETY: The caller of `got_hook` guarantees the import has the signature `F`.
+        unsafe { transmute_copy(&self.original) }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for GotHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| entry.address as *const usize)
+            .collect();
+
+        f.debug_struct("GotHook")
+            .field("entries", &entries)
+            .field("original", &(self.original as *const u8))
+            .finish()
+    }
+}
+
+/// Redirects the calls `module` makes to the imported `symbol` to `replacement`.
+///
+/// The entries are found through the `.rela.plt` and `.rela.dyn` relocations of the module. Pages
+/// that RELRO made read-only are writable just while an entry is replaced.
+///
+/// # Safety
+/// `module` must still be loaded and the imported function must have the signature `F`.
+pub unsafe fn got_hook<F: FnPtr>(
+    module: &Module,
+    symbol: &str,
+    replacement: F,
+) -> Result<GotHook<F>, Error> {
+    let (bias, headers) = loaded(module.base.as_usize()).ok_or(HookError::NotImported)?;
+
+    // SAFETY: The caller guarantees the module is still loaded.
+    let mut addresses: Vec<usize> = unsafe { entries(bias, headers) }
+        .into_iter()
+        .filter(|(name, _)| name.to_bytes() == symbol.as_bytes())
+        .map(|(_, address)| address)
+        .collect();
+
+    // Some linkers place `.rela.plt` inside the range of `DT_RELA`, which lists its entries twice.
+    // A second replacement would take the first one for the original.
+    addresses.sort_unstable();
+    addresses.dedup();
+
+    if addresses.is_empty() {
+        return Err(HookError::NotImported.into());
+    }
+
+    // With lazy binding an entry that was never called still points back into the PLT of the
+    // module, which would overwrite the hook once called. Resolve the function the same way instead.
+    let image = image_start(bias, headers)..image_end(bias, headers);
+    let original = addresses
+        .iter()
+        .map(|&address| unsafe { *(address as *const usize) })
+        .find(|original| !image.contains(original))
+        .or_else(|| {
+            let symbol = CString::new(symbol).ok()?;
+            let address = unsafe { dlsym(RTLD_DEFAULT, symbol.as_ptr()) };
+            (!address.is_null()).then_some(address as usize)
+        })
+        .ok_or(HookError::NotImported)?;
+
+    // SAFETY: `FnPtr` is only implemented for function pointers.
+    let replacement = unsafe { transmute_copy::<F, usize>(&replacement) };
+    let entries = addresses
+        .into_iter()
+        .map(|address| unsafe { Entry::replace(address as *mut usize, replacement) })
+        .collect::<Result<_, _>>()?;
+
+    Ok(GotHook {
+        entries,
+        original,
+        function: PhantomData,
+    })
+}
+
+/// Returns the load bias and program headers of the loaded module whose lowest segment starts at
+/// `base`.
+fn loaded(base: usize) -> Option<(usize, &'static [Elf64_Phdr])> {
+    unsafe extern "C" fn callback(
+        info: *mut dl_phdr_info,
+        _size: size_t,
+        data: *mut c_void,
+    ) -> c_int {
+        let (base, found) =
+            unsafe { &mut *(data as *mut (usize, Option<(usize, &'static [Elf64_Phdr])>)) };
+        let info = unsafe { &*info };
+
+        let bias = info.dlpi_addr as usize;
+        let headers = unsafe { from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize) };
+
+        if image_start(bias, headers) == *base {
+            *found = Some((bias, headers));
+            return 1;
+        }
+
+        0
+    }
+
+    let mut data = (base, None);
+    unsafe { dl_iterate_phdr(Some(callback), &mut data as *mut _ as *mut c_void) };
+    data.1
+}
+
+/// Returns the symbol name and address of each GOT entry the dynamic linker fills in for the
+/// module loaded with `bias`.
+///
+/// # Safety
+/// `headers` must be the program headers of a module that is still loaded.
+unsafe fn entries(bias: usize, headers: &[Elf64_Phdr]) -> Vec<(&'static CStr, usize)> {
+    let Some(dynamic) = headers.iter().find(|header| header.p_type == PT_DYNAMIC) else {
+        return Vec::new();
+    };
+
+    // glibc relocates the addresses in the dynamic section of every module it loads in place. The
+    // vDSO is mapped by the kernel, its read-only dynamic section stays relative to the bias.
+    // `modules` leaves it out, but its base can still end up in a `Module`.
+    let vdso = image_start(bias, headers) == unsafe { getauxval(AT_SYSINFO_EHDR) } as usize;
+    let address = |value: u64| match vdso {
+        true => bias + value as usize,
+        false => value as usize,
+    };
+
+    let (mut strings, mut symbols) = (0, 0);
+    let (mut plt, mut plt_len, mut rela, mut rela_len) = (0, 0, 0, 0);
+
+    let mut entry = (bias + dynamic.p_vaddr as usize) as *const Dyn;
+    loop {
+        let Dyn { tag, value } = unsafe { entry.read() };
+        match tag {
+            DT_NULL => break,
+            DT_STRTAB => strings = address(value),
+            DT_SYMTAB => symbols = address(value),
+            DT_JMPREL => plt = address(value),
+            DT_PLTRELSZ => plt_len = value as usize,
+            DT_RELA => rela = address(value),
+            DT_RELASZ => rela_len = value as usize,
+            _ => {}
+        }
+
+        entry = unsafe { entry.add(1) };
+    }
+
+    if strings == 0 || symbols == 0 {
+        return Vec::new();
+    }
+
+    let table = |start: usize, len: usize| match start {
+        0 => &[][..],
+        start => unsafe { from_raw_parts(start as *const Rela, len / size_of::<Rela>()) },
+    };
+
+    table(plt, plt_len)
+        .iter()
+        .chain(table(rela, rela_len))
+        .filter(|rela| matches!(rela.info as u32, R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT))
+        .map(|rela| {
+            let symbol = symbols + (rela.info >> 32) as usize * SYMBOL_LEN;
+            let name = unsafe { *(symbol as *const u32) } as usize;
+            let name = unsafe { CStr::from_ptr((strings + name) as *const _) };
+
+            (name, bias + rela.offset as usize)
+        })
+        .collect()
+}
@@ -0,0 +1,95 @@
+//! Hooks that replace entries of the import address table of a module.
+
+use core::fmt;
+use std::marker::PhantomData;
+use std::mem::transmute_copy;
+
+use super::entry::Entry;
+use super::HookError;
+use crate::ptr::{pe, FnPtr, Module};
+use crate::Error;
+
+/// A function imported by a module whose entry in the import address table points to a
+/// replacement.
+///
+/// Only calls made by that module are redirected, and the entry is restored when the hook is
+/// dropped.
+///
+/// # Examples
+/// ```no_run
+/// use std::sync::OnceLock;
+/// use transcend::ptr::{iat_hook, module, IatHook};
+///
+/// type Sleep = unsafe extern "win64" fn(u32);
+///
+/// static SLEEP: OnceLock<IatHook<Sleep>> = OnceLock::new();
+///
+/// unsafe extern "win64" fn sleep(milliseconds: u32) {
+///     // Never let the game wait longer than a frame.
+///     unsafe { (SLEEP.get().unwrap().original())(milliseconds.min(16)) }
+/// }
+///
+/// unsafe {
+///     let game = module("game.exe").unwrap();
+///     SLEEP.get_or_init(|| iat_hook(&game, "kernel32.dll", "Sleep", sleep as Sleep).unwrap());
+/// }
+/// ```
+pub struct IatHook<F: FnPtr> {
+    entry: Entry,
+    function: PhantomData<F>,
+}
+
+impl<F: FnPtr> IatHook<F> {
+    /// Returns the function the entry pointed to before.
+    #[must_use]
+    pub fn original(&self) -> F {
+        // SAFETY: The caller of `iat_hook` guarantees the import has the signature `F`.
+        unsafe { transmute_copy(&self.entry.original) }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for IatHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IatHook")
+            .field("entry", &(self.entry.address as *const usize))
+            .field("original", &(self.entry.original as *const u8))
+            .field("replacement", &(self.entry.replacement as *const u8))
+            .finish()
+    }
+}
+
+/// Redirects the calls `module` makes to `function`, imported from the module named `import`, to
+/// `replacement`.
+///
+/// Module names are compared case-insensitively, matching the loader. Functions imported by
+/// ordinal can not be found by name.
+///
+/// # Safety
+/// `module` must still be loaded and the imported function must have the signature `F`.
+pub unsafe fn iat_hook<F: FnPtr>(
+    module: &Module,
+    import: &str,
+    function: &str,
+    replacement: F,
+) -> Result<IatHook<F>, Error> {
+    // SAFETY: The caller guarantees the module is still loaded.
+    let imports = unsafe { pe::mapped_imports(module.base) };
+
+    let import = imports
+        .iter()
+        .find(|candidate| {
+            candidate.module.eq_ignore_ascii_case(import)
+                && candidate.name.as_deref() == Some(function)
+        })
+        .ok_or(HookError::NotImported)?;
+
+    // SAFETY: `FnPtr` is only implemented for function pointers.
+    let replacement = unsafe { transmute_copy::<F, usize>(&replacement) };
+    let address = (module.base + import.iat).as_mut_ptr();
+    let entry = unsafe { Entry::replace(address, replacement)? };
+
+    Ok(IatHook {
+        entry,
+        function: PhantomData,
+    })
+}
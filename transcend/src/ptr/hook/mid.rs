@@ -0,0 +1,306 @@
+//! Hooks in the middle of a function that hand all registers to a callback.
+
+use core::fmt;
+use std::arch::naked_asm;
+use std::mem::{offset_of, transmute};
+use std::ptr::copy_nonoverlapping;
+
+use super::{allocator, Detour, HookError};
+use crate::ptr::Address;
+use crate::Error;
+
+/// The registers at the hooked instruction, which the callback may change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C, align(16))]
+pub struct Context {
+    pub xmm: [u128; 16],
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rbx: u64,
+    /// Changes to the stack pointer are ignored.
+    pub rsp: u64,
+    pub rbp: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+    pub rflags: u64,
+}
+
+type Thunk = unsafe extern "C" fn();
+
+type Callback = dyn Fn(&mut Context) + Send + Sync;
+
+/// What [`stub`] passes to [`dispatch`].
+struct Inner {
+    callback: Box<Callback>,
+    trampoline: usize,
+}
+
+/// `lea rsp, [rsp - 128]` to step over the red zone, `push [rip + 13]` for the [`Inner`] of the
+/// hook and `jmp [rip + 15]` to [`stub`], followed by both addresses.
+const THUNK: [u8; 40] = [
+    0x48, 0x8D, 0x64, 0x24, 0x80, // lea rsp, [rsp - 128]
+    0xFF, 0x35, 0x0D, 0x00, 0x00, 0x00, // push [rip + 13]
+    0xFF, 0x25, 0x0F, 0x00, 0x00, 0x00, // jmp [rip + 15]
+    0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC, // padding
+    0, 0, 0, 0, 0, 0, 0, 0, // the inner hook
+    0, 0, 0, 0, 0, 0, 0, 0, // the stub
+];
+
+/// A callback that runs whenever execution reaches an instruction, with access to every register.
+///
+/// The hook is removed when it is dropped.
+///
+/// # Examples
+/// ```no_run
+/// use transcend::ptr::{base, mid_hook, Rva};
+///
+/// # fn main() -> Result<(), transcend::Error> {
+/// unsafe {
+///     // The game keeps the health of the player in `xmm1` while it applies damage.
+///     let hook = mid_hook(base()? + Rva::new(0x2843C7), |context| {
+///         context.xmm[1] = f32::to_bits(100.0) as u128;
+///     })?;
+///
+///     std::mem::forget(hook);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct MidHook {
+    detour: Detour<Thunk>,
+    thunk: usize,
+    inner: *mut Inner,
+}
+
+// SAFETY: The callback is `Send + Sync` and everything else is plain addresses.
+unsafe impl Send for MidHook {}
+unsafe impl Sync for MidHook {}
+
+/// Runs `callback` every time execution reaches `address`, before the instruction there.
+///
+/// The instructions at `address` are moved into a trampoline the same way [`Detour::new`] moves the
+/// start of a function. General-purpose registers, `rflags` and `xmm0` to `xmm15` are saved into a
+/// [`Context`] for the callback and restored from it afterwards. The upper halves of `ymm` and
+/// `zmm` registers are not saved.
+///
+/// # Safety
+/// `address` must point to the start of an instruction, no other instruction may branch into the
+/// bytes the jump replaces and no thread may be executing them while the hook is installed.
+pub unsafe fn mid_hook(
+    address: Address,
+    callback: impl Fn(&mut Context) + Send + Sync + 'static,
+) -> Result<MidHook, Error> {
+    let thunk = allocator::allocate(address.as_usize()).ok_or(HookError::Allocate)?;
+
+    // SAFETY: `Thunk` has the size of an address, whose code `Detour` only jumps to.
+    let target = unsafe { address.as_fn::<Thunk>() };
+    let detour = match unsafe { Detour::new_disabled(target, transmute::<usize, Thunk>(thunk)) } {
+        Ok(detour) => detour,
+        Err(error) => {
+            unsafe { allocator::free(thunk) };
+            return Err(error);
+        }
+    };
+
+    let inner = Box::into_raw(Box::new(Inner {
+        callback: Box::new(callback),
+        trampoline: detour.original() as usize,
+    }));
+
+    let mut code = THUNK;
+    code[24..32].copy_from_slice(&(inner as usize).to_ne_bytes());
+    code[32..40].copy_from_slice(&(stub as *const () as usize).to_ne_bytes());
+    unsafe { copy_nonoverlapping(code.as_ptr(), thunk as *mut u8, code.len()) };
+
+    let hook = MidHook {
+        detour,
+        thunk,
+        inner,
+    };
+
+    unsafe { hook.enable()? };
+    Ok(hook)
+}
+
+impl MidHook {
+    /// Redirects the hooked instruction to the callback.
+    ///
+    /// # Safety
+    /// See [`Detour::enable`].
+    pub unsafe fn enable(&self) -> Result<(), Error> {
+        unsafe { self.detour.enable() }
+    }
+
+    /// Restores the original instructions, the callback no longer runs.
+    ///
+    /// # Safety
+    /// See [`Detour::disable`].
+    pub unsafe fn disable(&self) -> Result<(), Error> {
+        unsafe { self.detour.disable() }
+    }
+
+    /// Returns whether the callback runs.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.detour.is_enabled()
+    }
+}
+
+impl Drop for MidHook {
+    fn drop(&mut self) {
+        // The thunk and callback have to stay around if the code still jumps to them. The detour
+        // itself is dropped right after.
+        if unsafe { self.detour.disable() }.is_ok() {
+            unsafe { allocator::free(self.thunk) };
+            drop(unsafe { Box::from_raw(self.inner) });
+        }
+    }
+}
+
+impl fmt::Debug for MidHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MidHook")
+            .field("address", &(self.detour.target() as *const u8))
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+/// Runs the callback of `inner` and returns where execution continues.
+extern "C" fn dispatch(context: &mut Context, inner: &Inner) -> usize {
+    (inner.callback)(context);
+    inner.trampoline
+}
+
+/// Saves the registers into a [`Context`] on the stack, calls [`dispatch`] and continues at the
+/// address it returns with the registers restored.
+///
+/// The thunk enters with `rsp` 128 bytes below the hooked instruction and the [`Inner`] pushed.
+#[unsafe(naked)]
+unsafe extern "C" fn stub() {
+    naked_asm!(
+        "pushfq",
+        // The callback is Rust code, which expects the direction flag to be clear.
+        "cld",
+        "push rbx",
+        // `rbx` points to the saved `rbx`, above it are `rflags`, the inner hook and the red zone.
+        "mov rbx, rsp",
+        "and rsp, -16",
+        "sub rsp, {size}",
+        "movaps [rsp + {xmm}], xmm0",
+        "movaps [rsp + {xmm} + 16], xmm1",
+        "movaps [rsp + {xmm} + 32], xmm2",
+        "movaps [rsp + {xmm} + 48], xmm3",
+        "movaps [rsp + {xmm} + 64], xmm4",
+        "movaps [rsp + {xmm} + 80], xmm5",
+        "movaps [rsp + {xmm} + 96], xmm6",
+        "movaps [rsp + {xmm} + 112], xmm7",
+        "movaps [rsp + {xmm} + 128], xmm8",
+        "movaps [rsp + {xmm} + 144], xmm9",
+        "movaps [rsp + {xmm} + 160], xmm10",
+        "movaps [rsp + {xmm} + 176], xmm11",
+        "movaps [rsp + {xmm} + 192], xmm12",
+        "movaps [rsp + {xmm} + 208], xmm13",
+        "movaps [rsp + {xmm} + 224], xmm14",
+        "movaps [rsp + {xmm} + 240], xmm15",
+        "mov [rsp + {rax}], rax",
+        "mov [rsp + {rcx}], rcx",
+        "mov [rsp + {rdx}], rdx",
+        "mov rax, [rbx]",
+        "mov [rsp + {rbx}], rax",
+        "lea rax, [rbx + 152]",
+        "mov [rsp + {rsp}], rax",
+        "mov [rsp + {rbp}], rbp",
+        "mov [rsp + {rsi}], rsi",
+        "mov [rsp + {rdi}], rdi",
+        "mov [rsp + {r8}], r8",
+        "mov [rsp + {r9}], r9",
+        "mov [rsp + {r10}], r10",
+        "mov [rsp + {r11}], r11",
+        "mov [rsp + {r12}], r12",
+        "mov [rsp + {r13}], r13",
+        "mov [rsp + {r14}], r14",
+        "mov [rsp + {r15}], r15",
+        "mov rax, [rbx + 8]",
+        "mov [rsp + {rflags}], rax",
+        // Both arguments in the registers of both the System V and the Windows convention, with
+        // shadow space for the latter.
+        "mov rdi, rsp",
+        "mov rcx, rsp",
+        "mov rsi, [rbx + 16]",
+        "mov rdx, [rbx + 16]",
+        "sub rsp, 32",
+        "call {dispatch}",
+        "add rsp, 32",
+        // Return to the trampoline in place of the inner hook.
+        "mov [rbx + 16], rax",
+        "movaps xmm0, [rsp + {xmm}]",
+        "movaps xmm1, [rsp + {xmm} + 16]",
+        "movaps xmm2, [rsp + {xmm} + 32]",
+        "movaps xmm3, [rsp + {xmm} + 48]",
+        "movaps xmm4, [rsp + {xmm} + 64]",
+        "movaps xmm5, [rsp + {xmm} + 80]",
+        "movaps xmm6, [rsp + {xmm} + 96]",
+        "movaps xmm7, [rsp + {xmm} + 112]",
+        "movaps xmm8, [rsp + {xmm} + 128]",
+        "movaps xmm9, [rsp + {xmm} + 144]",
+        "movaps xmm10, [rsp + {xmm} + 160]",
+        "movaps xmm11, [rsp + {xmm} + 176]",
+        "movaps xmm12, [rsp + {xmm} + 192]",
+        "movaps xmm13, [rsp + {xmm} + 208]",
+        "movaps xmm14, [rsp + {xmm} + 224]",
+        "movaps xmm15, [rsp + {xmm} + 240]",
+        "mov rax, [rsp + {rflags}]",
+        "mov [rbx + 8], rax",
+        "mov rax, [rsp + {rbx}]",
+        "mov [rbx], rax",
+        "mov rcx, [rsp + {rcx}]",
+        "mov rdx, [rsp + {rdx}]",
+        "mov rbp, [rsp + {rbp}]",
+        "mov rsi, [rsp + {rsi}]",
+        "mov rdi, [rsp + {rdi}]",
+        "mov r8, [rsp + {r8}]",
+        "mov r9, [rsp + {r9}]",
+        "mov r10, [rsp + {r10}]",
+        "mov r11, [rsp + {r11}]",
+        "mov r12, [rsp + {r12}]",
+        "mov r13, [rsp + {r13}]",
+        "mov r14, [rsp + {r14}]",
+        "mov r15, [rsp + {r15}]",
+        "mov rax, [rsp + {rax}]",
+        "mov rsp, rbx",
+        "pop rbx",
+        "popfq",
+        // Pops the trampoline and steps back over the red zone.
+        "ret 128",
+        size = const size_of::<Context>(),
+        xmm = const offset_of!(Context, xmm),
+        rax = const offset_of!(Context, rax),
+        rcx = const offset_of!(Context, rcx),
+        rdx = const offset_of!(Context, rdx),
+        rbx = const offset_of!(Context, rbx),
+        rsp = const offset_of!(Context, rsp),
+        rbp = const offset_of!(Context, rbp),
+        rsi = const offset_of!(Context, rsi),
+        rdi = const offset_of!(Context, rdi),
+        r8 = const offset_of!(Context, r8),
+        r9 = const offset_of!(Context, r9),
+        r10 = const offset_of!(Context, r10),
+        r11 = const offset_of!(Context, r11),
+        r12 = const offset_of!(Context, r12),
+        r13 = const offset_of!(Context, r13),
+        r14 = const offset_of!(Context, r14),
+        r15 = const offset_of!(Context, r15),
+        rflags = const offset_of!(Context, rflags),
+        dispatch = sym dispatch,
+    );
+}
@@ -0,0 +1,203 @@
+//! Bytes written over code that can be switched on and off, without a trampoline.
+
+use core::fmt;
+use std::slice::from_raw_parts;
+use std::sync::{Mutex, PoisonError};
+
+use super::{jump, write};
+use crate::ptr::Address;
+use crate::Error;
+
+/// The `nop`s of one to nine bytes the Intel optimization manual recommends.
+const NOPS: [&[u8]; 9] = [
+    &[0x90],
+    &[0x66, 0x90],
+    &[0x0F, 0x1F, 0x00],
+    &[0x0F, 0x1F, 0x40, 0x00],
+    &[0x0F, 0x1F, 0x44, 0x00, 0x00],
+    &[0x66, 0x0F, 0x1F, 0x44, 0x00, 0x00],
+    &[0x0F, 0x1F, 0x80, 0x00, 0x00, 0x00, 0x00],
+    &[0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+    &[0x66, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+const RET: u8 = 0xC3;
+
+/// Bytes written over code, which keeps the bytes they replaced to switch back.
+///
+/// The original bytes are restored when the patch is dropped.
+///
+/// # Examples
+/// ```no_run
+/// use transcend::ptr::{base, Patch, Rva};
+///
+/// # fn main() -> Result<(), transcend::Error> {
+/// unsafe {
+///     // The 5 byte call that takes away ammunition after every shot.
+///     let patch = Patch::nop(base()? + Rva::new(0x1C2F0A), 5)?;
+///
+///     std::mem::forget(patch);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Patch {
+    address: Address,
+    original: Box<[u8]>,
+    bytes: Box<[u8]>,
+    enabled: Mutex<bool>,
+}
+
+impl Patch {
+    /// Writes `bytes` over the code at `address`.
+    ///
+    /// # Safety
+    /// See [`new_disabled`](Self::new_disabled) and [`enable`](Self::enable).
+    pub unsafe fn new(address: Address, bytes: &[u8]) -> Result<Self, Error> {
+        let patch = unsafe { Self::new_disabled(address, bytes) };
+        unsafe { patch.enable()? };
+        Ok(patch)
+    }
+
+    /// Remembers the code at `address` but leaves it untouched until the patch is enabled.
+    ///
+    /// # Safety
+    /// `address` must point to `bytes.len()` bytes of code, and `bytes` must end where an
+    /// instruction of the original code ends.
+    #[must_use]
+    pub unsafe fn new_disabled(address: Address, bytes: &[u8]) -> Self {
+        Self {
+            address,
+            original: unsafe { from_raw_parts(address.as_ptr(), bytes.len()) }.into(),
+            bytes: bytes.into(),
+            enabled: Mutex::new(false),
+        }
+    }
+
+    /// Replaces the `len` bytes at `address` with as few `nop`s as possible.
+    ///
+    /// # Safety
+    /// See [`new`](Self::new).
+    pub unsafe fn nop(address: Address, len: usize) -> Result<Self, Error> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let nop = NOPS[(len - bytes.len()).min(NOPS.len()) - 1];
+            bytes.extend_from_slice(nop);
+        }
+
+        unsafe { Self::new(address, &bytes) }
+    }
+
+    /// Makes the function at `address` return right away.
+    ///
+    /// # Safety
+    /// See [`new`](Self::new). Whatever the function leaves in the return registers is returned,
+    /// and the function may not need to clean up its arguments.
+    pub unsafe fn ret(address: Address) -> Result<Self, Error> {
+        unsafe { Self::new(address, &[RET]) }
+    }
+
+    /// Makes the code at `address` jump to `destination`, with `jmp rel32` if it is within reach
+    /// and a 14 byte `jmp [rip + 0]` otherwise.
+    ///
+    /// # Safety
+    /// See [`new`](Self::new).
+    pub unsafe fn jmp(address: Address, destination: Address) -> Result<Self, Error> {
+        let bytes = jump(address.as_usize(), destination.as_usize());
+        unsafe { Self::new(address, &bytes) }
+    }
+
+    /// Returns the address of the patched code.
+    #[must_use]
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Returns the bytes the patch replaced.
+    #[must_use]
+    pub fn original(&self) -> &[u8] {
+        &self.original
+    }
+
+    /// Returns the bytes of the patch.
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Writes the patch over the code.
+    ///
+    /// # Safety
+    /// No thread may be executing the patched bytes.
+    pub unsafe fn enable(&self) -> Result<(), Error> {
+        let mut enabled = self.enabled.lock().unwrap_or_else(PoisonError::into_inner);
+        if !*enabled {
+            unsafe { write(self.address.as_mut_ptr(), &self.bytes)? };
+            *enabled = true;
+        }
+
+        Ok(())
+    }
+
+    /// Restores the original code.
+    ///
+    /// # Safety
+    /// No thread may be executing the patched bytes.
+    pub unsafe fn disable(&self) -> Result<(), Error> {
+        let mut enabled = self.enabled.lock().unwrap_or_else(PoisonError::into_inner);
+        if *enabled {
+            unsafe { write(self.address.as_mut_ptr(), &self.original)? };
+            *enabled = false;
+        }
+
+        Ok(())
+    }
+
+    /// Enables the patch if it is disabled and the other way around, and returns whether it is
+    /// enabled now.
+    ///
+    /// # Safety
+    /// No thread may be executing the patched bytes.
+    pub unsafe fn toggle(&self) -> Result<bool, Error> {
+        let mut enabled = self.enabled.lock().unwrap_or_else(PoisonError::into_inner);
+        let bytes = match *enabled {
+            true => &self.original,
+            false => &self.bytes,
+        };
+
+        unsafe { write(self.address.as_mut_ptr(), bytes)? };
+        *enabled = !*enabled;
+        Ok(*enabled)
+    }
+
+    /// Returns whether the patch is written over the code.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        *self.enabled.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Restores the original code for good, unlike dropping the patch reporting whether it worked.
+    ///
+    /// # Safety
+    /// No thread may be executing the patched bytes.
+    pub unsafe fn revert(self) -> Result<(), Error> {
+        unsafe { self.disable() }
+    }
+}
+
+impl Drop for Patch {
+    fn drop(&mut self) {
+        let _ = unsafe { self.disable() };
+    }
+}
+
+impl fmt::Debug for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Patch")
+            .field("address", &self.address())
+            .field("original", &self.original)
+            .field("bytes", &self.bytes)
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
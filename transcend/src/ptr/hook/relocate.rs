@@ -0,0 +1,403 @@
+//! Moves instructions into a trampoline.
+//!
+//! Relative branches and RIP-relative operands are rewritten so they still reach their original
+//! target from the new address. Short branches are widened and anything out of the ±2 GiB reach
+//! of a 32-bit displacement is turned into an absolute form.
+
+use super::decode::{decode, Flow};
+use super::HookError;
+
+/// The most an instruction can grow by when it is moved.
+const MAX_GROWTH: usize = 16;
+
+/// Instructions rewritten by [`relocate`].
+pub(crate) struct Relocated {
+    pub(crate) code: Vec<u8>,
+    /// The offset of each original instruction and of its moved copy in `code`.
+    pub(crate) boundaries: Vec<(usize, usize)>,
+}
+
+/// Rewrites the instructions in `code`, which start at `from`, to run from `to`.
+///
+/// Branches to an instruction in `code` are redirected to its moved copy.
+pub(crate) fn relocate(code: &[u8], from: usize, to: usize) -> Result<Relocated, HookError> {
+    let mut relocator = Relocator {
+        code,
+        from,
+        to,
+        out: Vec::with_capacity(code.len() + MAX_GROWTH),
+        boundaries: Vec::new(),
+        fixups: Vec::new(),
+    };
+
+    let mut offset = 0;
+    while offset < code.len() {
+        let instruction = decode(&code[offset..]).ok_or(HookError::Decode { offset })?;
+        let bytes = &code[offset..offset + instruction.len];
+        let address = from + offset;
+
+        relocator.boundaries.push((offset, relocator.out.len()));
+
+        if let Some(target) = instruction.branch_target(address, bytes) {
+            match instruction.flow {
+                Flow::Jump => relocator.jump(target),
+                Flow::Branch { condition } => relocator.branch(condition, target),
+                Flow::Call => relocator.call(target),
+                Flow::Loop => relocator.loop_branch(&bytes[..instruction.len - 1], target),
+                _ => unreachable!("only branches have a relative target"),
+            }
+        } else if let (Some(displacement), Some(target)) = (
+            instruction.rip_relative,
+            instruction.rip_target(address, bytes),
+        ) {
+            if !relocator.rip_relative(bytes, displacement, target) {
+                return Err(HookError::Relocate { offset });
+            }
+        } else {
+            relocator.out.extend_from_slice(bytes);
+        }
+
+        offset += instruction.len;
+    }
+
+    relocator.finish()
+}
+
+struct Relocator<'a> {
+    code: &'a [u8],
+    from: usize,
+    to: usize,
+    out: Vec<u8>,
+    /// Where each instruction of `code` starts in `out`.
+    boundaries: Vec<(usize, usize)>,
+    /// A 32-bit displacement in `out` that still has to point to an offset in `code`.
+    fixups: Vec<(usize, usize)>,
+}
+
+impl Relocator<'_> {
+    /// Returns the offset of `target` in `code`, if the branch stays within the moved instructions.
+    fn internal(&self, target: usize) -> Option<usize> {
+        let offset = target.wrapping_sub(self.from);
+        (offset < self.code.len()).then_some(offset)
+    }
+
+    /// Returns the displacement from the end of an instruction of `len` bytes emitted next to
+    /// `target`, if it fits into 32 bits.
+    fn displacement(&self, len: usize, target: usize) -> Option<i32> {
+        let end = self.to + self.out.len() + len;
+        i32::try_from(target.wrapping_sub(end) as isize).ok()
+    }
+
+    /// Emits a 32-bit displacement to `target`, which lies in `code`.
+    fn fixup(&mut self, target: usize) {
+        self.fixups.push((self.out.len(), target));
+        self.out.extend_from_slice(&[0; 4]);
+    }
+
+    /// Emits `jmp rel32`, or `jmp [rip + 0]` with the absolute address when out of range.
+    fn jump(&mut self, target: usize) {
+        if let Some(offset) = self.internal(target) {
+            self.out.push(0xE9);
+            self.fixup(offset);
+        } else if let Some(displacement) = self.displacement(5, target) {
+            self.out.push(0xE9);
+            self.out.extend_from_slice(&displacement.to_le_bytes());
+        } else {
+            self.out
+                .extend_from_slice(&[0xFF, 0x25, 0x00, 0x00, 0x00, 0x00]);
+            self.out.extend_from_slice(&target.to_le_bytes());
+        }
+    }
+
+    /// Emits `jcc rel32`, or the inverted `jcc rel8` over an absolute jump when out of range.
+    fn branch(&mut self, condition: u8, target: usize) {
+        if let Some(offset) = self.internal(target) {
+            self.out.extend_from_slice(&[0x0F, 0x80 | condition]);
+            self.fixup(offset);
+        } else if let Some(displacement) = self.displacement(6, target) {
+            self.out.extend_from_slice(&[0x0F, 0x80 | condition]);
+            self.out.extend_from_slice(&displacement.to_le_bytes());
+        } else {
+            // Flipping the lowest bit inverts the condition.
+            self.out.extend_from_slice(&[0x70 | (condition ^ 1), 0x00]);
+            self.skip(|relocator| relocator.jump(target));
+        }
+    }
+
+    /// Emits `call rel32`, or `call [rip + 2]` that skips over the absolute address when out of range.
+    fn call(&mut self, target: usize) {
+        if let Some(offset) = self.internal(target) {
+            self.out.push(0xE8);
+            self.fixup(offset);
+        } else if let Some(displacement) = self.displacement(5, target) {
+            self.out.push(0xE8);
+            self.out.extend_from_slice(&displacement.to_le_bytes());
+        } else {
+            self.out
+                .extend_from_slice(&[0xFF, 0x15, 0x02, 0x00, 0x00, 0x00, 0xEB, 0x08]);
+            self.out.extend_from_slice(&target.to_le_bytes());
+        }
+    }
+
+    /// `loop` and `jrcxz` only take an 8-bit displacement, so they branch to a jump right behind
+    /// them which is otherwise skipped.
+    fn loop_branch(&mut self, opcode: &[u8], target: usize) {
+        self.out.extend_from_slice(opcode);
+        self.out.extend_from_slice(&[0x02, 0xEB, 0x00]);
+        self.skip(|relocator| relocator.jump(target));
+    }
+
+    /// Emits code with `emit` and points the 8-bit displacement right before it past the code.
+    fn skip(&mut self, emit: impl FnOnce(&mut Self)) {
+        let start = self.out.len();
+        emit(self);
+        self.out[start - 1] = (self.out.len() - start) as u8;
+    }
+
+    /// Moves an instruction with a `[rip + disp32]` operand, which is at `displacement` in `bytes`.
+    ///
+    /// Out of range `lea` and `mov` loads become a `mov` of the absolute address, anything else
+    /// can not be moved that far.
+    fn rip_relative(&mut self, bytes: &[u8], displacement: usize, target: usize) -> bool {
+        if let Some(value) = self.displacement(bytes.len(), target) {
+            self.out.extend_from_slice(bytes);
+            let at = self.out.len() - bytes.len() + displacement;
+            self.out[at..at + 4].copy_from_slice(&value.to_le_bytes());
+            return true;
+        }
+
+        let (rex, opcode, modrm) = match *bytes {
+            [rex @ 0x40..=0x4F, opcode, modrm, ..] if bytes.len() == 7 => (rex, opcode, modrm),
+            [opcode, modrm, ..] if bytes.len() == 6 => (0x40, opcode, modrm),
+            _ => return false,
+        };
+
+        let wide = rex & 0x08 != 0;
+        let reg = (rex & 0x04) << 1 | (modrm >> 3) & 0x07;
+        let extension = reg >> 3;
+
+        match opcode {
+            // lea reg, [rip + disp32]
+            0x8D if wide => {
+                self.out
+                    .extend_from_slice(&[0x48 | extension, 0xB8 | (reg & 0x07)]);
+                self.out.extend_from_slice(&target.to_le_bytes());
+            }
+            0x8D => {
+                // 32-bit results only keep the low half of the address.
+                if extension != 0 {
+                    self.out.push(0x41);
+                }
+                self.out.push(0xB8 | (reg & 0x07));
+                self.out.extend_from_slice(&(target as u32).to_le_bytes());
+            }
+            // mov reg, [rip + disp32]
+            0x8B => {
+                self.out
+                    .extend_from_slice(&[0x48 | extension, 0xB8 | (reg & 0x07)]);
+                self.out.extend_from_slice(&target.to_le_bytes());
+
+                // mov reg, [reg]
+                self.out
+                    .push(rex & 0x08 | 0x40 | extension << 2 | extension);
+                self.out.push(0x8B);
+                match reg & 0x07 {
+                    // `rsp` and `r12` need a SIB byte.
+                    4 => self.out.extend_from_slice(&[0x24, 0x24]),
+                    // `rbp` and `r13` without displacement mean `rip`.
+                    5 => self.out.extend_from_slice(&[0x6D, 0x00]),
+                    reg => self.out.push(reg << 3 | reg),
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn finish(mut self) -> Result<Relocated, HookError> {
+        for (at, target) in self.fixups {
+            // Branches into the middle of an instruction can not be followed.
+            let index = self
+                .boundaries
+                .binary_search_by_key(&target, |&(offset, _)| offset)
+                .map_err(|_| HookError::Relocate { offset: target })?;
+
+            let displacement = self.boundaries[index].1 as i32 - (at + 4) as i32;
+            self.out[at..at + 4].copy_from_slice(&displacement.to_le_bytes());
+        }
+
+        Ok(Relocated {
+            code: self.out,
+            boundaries: self.boundaries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where the tested code pretends to live, with a trampoline out of reach of any rel32.
+    const FROM: usize = 0x7FF6_1000_0000;
+    const FAR: usize = 0x1000_0000;
+
+    fn far(code: &[u8]) -> Vec<u8> {
+        relocate(code, FROM, FAR).unwrap().code
+    }
+
+    /// `jmp [rip + 0]` to `target`.
+    fn absolute(target: usize) -> Vec<u8> {
+        let mut jump = vec![0xFF, 0x25, 0x00, 0x00, 0x00, 0x00];
+        jump.extend_from_slice(&target.to_le_bytes());
+        jump
+    }
+
+    fn concat(parts: &[&[u8]]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    #[test]
+    fn near() {
+        let to = FROM + 0x1000;
+
+        // jmp +0x10 widens to a rel32 from the trampoline.
+        let code = relocate(&[0xEB, 0x10], FROM, to).unwrap().code;
+        let displacement = (FROM + 0x12).wrapping_sub(to + 5) as i32;
+        assert_eq!(code, concat(&[&[0xE9], &displacement.to_le_bytes()]));
+
+        // mov rax, [rip + 0x10] keeps its form with a new displacement.
+        let code = relocate(&[0x48, 0x8B, 0x05, 0x10, 0x00, 0x00, 0x00], FROM, to).unwrap();
+        let displacement = (FROM + 0x17).wrapping_sub(to + 7) as i32;
+        assert_eq!(
+            code.code,
+            concat(&[&[0x48, 0x8B, 0x05], &displacement.to_le_bytes()])
+        );
+    }
+
+    #[test]
+    fn far_jump() {
+        assert_eq!(far(&[0xEB, 0x10]), absolute(FROM + 0x12));
+        assert_eq!(far(&[0xE9, 0x00, 0x01, 0x00, 0x00]), absolute(FROM + 0x105));
+    }
+
+    #[test]
+    fn far_branch() {
+        // je +0x10 becomes jne over an absolute jump.
+        let jump = absolute(FROM + 0x12);
+        assert_eq!(
+            far(&[0x74, 0x10]),
+            concat(&[&[0x75, jump.len() as u8], &jump])
+        );
+
+        // jg rel32 becomes jle over it.
+        let jump = absolute(FROM + 0x106);
+        assert_eq!(
+            far(&[0x0F, 0x8F, 0x00, 0x01, 0x00, 0x00]),
+            concat(&[&[0x7E, jump.len() as u8], &jump])
+        );
+    }
+
+    #[test]
+    fn far_call() {
+        // call [rip + 2], jmp +8 over the inline pointer that follows.
+        let target = FROM + 0x105;
+        assert_eq!(
+            far(&[0xE8, 0x00, 0x01, 0x00, 0x00]),
+            concat(&[
+                &[0xFF, 0x15, 0x02, 0x00, 0x00, 0x00, 0xEB, 0x08],
+                &target.to_le_bytes()
+            ])
+        );
+    }
+
+    #[test]
+    fn far_loop() {
+        // loop and jrcxz branch over a short jump to an absolute one.
+        let jump = absolute(FROM + 0x12);
+        assert_eq!(
+            far(&[0xE2, 0x10]),
+            concat(&[&[0xE2, 0x02, 0xEB, jump.len() as u8], &jump])
+        );
+
+        let jump = absolute(FROM + 0x13);
+        assert_eq!(
+            far(&[0x67, 0xE3, 0x10]),
+            concat(&[&[0x67, 0xE3, 0x02, 0xEB, jump.len() as u8], &jump])
+        );
+    }
+
+    #[test]
+    fn far_rip_relative() {
+        let target = FROM + 0x17;
+        let address = target.to_le_bytes();
+
+        // lea rax, [rip + 0x10] and lea r12, [rip + 0x10] load the address instead.
+        assert_eq!(
+            far(&[0x48, 0x8D, 0x05, 0x10, 0x00, 0x00, 0x00]),
+            concat(&[&[0x48, 0xB8], &address])
+        );
+        assert_eq!(
+            far(&[0x4C, 0x8D, 0x25, 0x10, 0x00, 0x00, 0x00]),
+            concat(&[&[0x49, 0xBC], &address])
+        );
+
+        // lea r13d, [rip + 0x10] only keeps the low half.
+        assert_eq!(
+            far(&[0x44, 0x8D, 0x2D, 0x10, 0x00, 0x00, 0x00]),
+            concat(&[&[0x41, 0xBD], &(target as u32).to_le_bytes()])
+        );
+
+        // mov r12, [rip + 0x10] and mov r13, [rip + 0x10] load the address and then through it,
+        // which needs a SIB byte for r12 and a zero displacement for r13.
+        assert_eq!(
+            far(&[0x4C, 0x8B, 0x25, 0x10, 0x00, 0x00, 0x00]),
+            concat(&[&[0x49, 0xBC], &address, &[0x4D, 0x8B, 0x24, 0x24]])
+        );
+        assert_eq!(
+            far(&[0x4C, 0x8B, 0x2D, 0x10, 0x00, 0x00, 0x00]),
+            concat(&[&[0x49, 0xBD], &address, &[0x4D, 0x8B, 0x6D, 0x00]])
+        );
+
+        // mov eax, [rip + 0x10]
+        let target = FROM + 0x16;
+        assert_eq!(
+            far(&[0x8B, 0x05, 0x10, 0x00, 0x00, 0x00]),
+            concat(&[&[0x48, 0xB8], &target.to_le_bytes(), &[0x40, 0x8B, 0x00]])
+        );
+
+        // Anything else can not be moved that far.
+        assert!(matches!(
+            relocate(&[0x90, 0x48, 0x03, 0x05, 0x10, 0x00, 0x00, 0x00], FROM, FAR),
+            Err(HookError::Relocate { offset: 1 })
+        ));
+        assert!(matches!(
+            relocate(&[0xFF, 0x15, 0x10, 0x00, 0x00, 0x00], FROM, FAR),
+            Err(HookError::Relocate { offset: 0 })
+        ));
+    }
+
+    #[test]
+    fn internal_branches() {
+        // je +1 to the second nop follows it to its moved copy, wherever the trampoline is.
+        let relocated = relocate(&[0x74, 0x01, 0x90, 0x90], FROM, FAR).unwrap();
+        assert_eq!(
+            relocated.code,
+            [0x0F, 0x84, 0x01, 0x00, 0x00, 0x00, 0x90, 0x90]
+        );
+        assert_eq!(relocated.boundaries, [(0, 0), (2, 6), (3, 7)]);
+
+        // loop back to the nop at the start.
+        let relocated = relocate(&[0x90, 0xE2, 0xFD], FROM, FAR).unwrap();
+        assert_eq!(
+            relocated.code,
+            [0x90, 0xE2, 0x02, 0xEB, 0x05, 0xE9, 0xF6, 0xFF, 0xFF, 0xFF]
+        );
+
+        // A branch into the middle of a moved instruction can not be followed.
+        assert!(matches!(
+            relocate(&[0x74, 0x01, 0x48, 0x8B, 0xC0], FROM, FAR),
+            Err(HookError::Relocate { offset: 3 })
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+//! Stopping the other threads of the process while code is patched.
+//!
+//! Windows suspends them, Linux sends each one a signal whose handler waits until the patch is
+//! done. Either way threads are moved out of replaced code before they continue.
+
+use super::HookError;
+
+#[cfg(target_os = "windows")]
+use windows::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    System::{
+        Diagnostics::{
+            Debug::{GetThreadContext, SetThreadContext, CONTEXT, CONTEXT_CONTROL_AMD64},
+            ToolHelp::{
+                CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD,
+                THREADENTRY32,
+            },
+        },
+        Threading::{
+            GetCurrentProcessId, GetCurrentThreadId, OpenThread, ResumeThread, SuspendThread,
+            THREAD_GET_CONTEXT, THREAD_SET_CONTEXT, THREAD_SUSPEND_RESUME,
+        },
+    },
+};
+
+#[cfg(target_os = "linux")]
+use {
+    libc::{
+        c_int, c_void, getpid, pid_t, sched_yield, sigaction, sigemptyset, siginfo_t, syscall,
+        ucontext_t, SYS_gettid, SYS_tgkill, REG_RIP, SA_RESTART, SA_SIGINFO, SIGRTMAX,
+    },
+    std::fs,
+    std::ptr::null_mut,
+    std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    std::sync::Once,
+    std::thread,
+    std::time::{Duration, Instant},
+};
+
+/// Moves an instruction pointer out of replaced code, if it is in there.
+pub(crate) type Fixup<'a> = &'a dyn Fn(usize) -> Option<usize>;
+
+/// The other threads of the process, stopped until [`Stopped::resume`].
+pub(crate) struct Stopped {
+    #[cfg(target_os = "windows")]
+    threads: Vec<HANDLE>,
+    /// The IDs the threads were found by, which are only freed once they run again.
+    #[cfg(target_os = "windows")]
+    ids: Vec<u32>,
+}
+
+#[cfg(target_os = "windows")]
+impl Stopped {
+    /// Suspends every other thread of the process.
+    pub(crate) unsafe fn stop() -> Result<Self, HookError> {
+        let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) }
+            .map_err(|_| HookError::Suspend)?;
+
+        let process = unsafe { GetCurrentProcessId() };
+        let current = unsafe { GetCurrentThreadId() };
+
+        let mut entry = THREADENTRY32 {
+            dwSize: size_of::<THREADENTRY32>() as u32,
+            ..Default::default()
+        };
+
+        let mut ids = Vec::new();
+        let mut next = unsafe { Thread32First(snapshot, &mut entry) };
+        while next.is_ok() {
+            if entry.th32OwnerProcessID == process && entry.th32ThreadID != current {
+                ids.push(entry.th32ThreadID);
+            }
+
+            next = unsafe { Thread32Next(snapshot, &mut entry) };
+        }
+
+        let _ = unsafe { CloseHandle(snapshot) };
+
+        // Nothing may allocate once the first thread is suspended, it could be holding the heap
+        // lock. The handles fit into the capacity reserved up front.
+        let mut stopped = Self {
+            threads: Vec::with_capacity(ids.len()),
+            ids,
+        };
+
+        let access = THREAD_SUSPEND_RESUME | THREAD_GET_CONTEXT | THREAD_SET_CONTEXT;
+        for &id in &stopped.ids {
+            // Threads that exited since the snapshot can not be opened anymore.
+            if let Ok(thread) = unsafe { OpenThread(access, false, id) } {
+                if unsafe { SuspendThread(thread) } == u32::MAX {
+                    let _ = unsafe { CloseHandle(thread) };
+                } else {
+                    // Suspension is asynchronous, reading the context waits until it happened.
+                    let _ = unsafe { context(thread) };
+                    stopped.threads.push(thread);
+                }
+            }
+        }
+
+        Ok(stopped)
+    }
+
+    /// Resumes the threads after moving them with `fixup`.
+    pub(crate) unsafe fn resume(self, fixup: Fixup<'_>) {
+        for thread in self.threads {
+            if let Some(mut context) = unsafe { context(thread) } {
+                if let Some(rip) = fixup(context.Rip as usize) {
+                    context.Rip = rip as u64;
+                    let _ = unsafe { SetThreadContext(thread, &context) };
+                }
+            }
+
+            unsafe { ResumeThread(thread) };
+            let _ = unsafe { CloseHandle(thread) };
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn context(thread: HANDLE) -> Option<CONTEXT> {
+    let mut context = CONTEXT {
+        ContextFlags: CONTEXT_CONTROL_AMD64,
+        ..Default::default()
+    };
+
+    unsafe { GetThreadContext(thread, &mut context) }
+        .ok()
+        .map(|()| context)
+}
+
+/// How long to wait for all threads to reach the signal handler.
+#[cfg(target_os = "linux")]
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The number of threads waiting in [`park`].
+#[cfg(target_os = "linux")]
+static PARKED: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the threads waiting in [`park`] may continue.
+#[cfg(target_os = "linux")]
+static RELEASED: AtomicBool = AtomicBool::new(true);
+
+/// The [`Fixup`] parked threads apply to themselves before they continue.
+#[cfg(target_os = "linux")]
+static FIXUP: AtomicPtr<Fixup<'static>> = AtomicPtr::new(null_mut());
+
+/// The signal that parks threads, picked from the end of the real-time range which runtimes
+/// rarely use.
+#[cfg(target_os = "linux")]
+fn signal() -> c_int {
+    SIGRTMAX() - 1
+}
+
+#[cfg(target_os = "linux")]
+impl Stopped {
+    /// Sends every other thread of the process a signal and waits until all of them are parked in
+    /// its handler.
+    pub(crate) unsafe fn stop() -> Result<Self, HookError> {
+        static HANDLER: Once = Once::new();
+
+        // The handler stays installed, a thread that only handles the signal after the patch was
+        // applied continues right away.
+        HANDLER.call_once(|| {
+            let mut action: sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = park as *const () as usize;
+            action.sa_flags = SA_SIGINFO | SA_RESTART;
+            unsafe { sigemptyset(&mut action.sa_mask) };
+            unsafe { sigaction(signal(), &action, null_mut()) };
+        });
+
+        let process = unsafe { getpid() };
+        let current = unsafe { syscall(SYS_gettid) } as pid_t;
+
+        let threads: Vec<pid_t> = fs::read_dir("/proc/self/task")
+            .map_err(|_| HookError::Suspend)?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter(|&thread| thread != current)
+            .collect();
+
+        FIXUP.store(null_mut(), Ordering::Release);
+        RELEASED.store(false, Ordering::Release);
+
+        // Threads that exited since they were listed can not be signalled anymore.
+        let signalled = threads
+            .into_iter()
+            .filter(|&thread| unsafe { syscall(SYS_tgkill, process, thread, signal()) } == 0)
+            .count();
+
+        let start = Instant::now();
+        while PARKED.load(Ordering::Acquire) < signalled {
+            if start.elapsed() > TIMEOUT {
+                // Some thread blocks the signal, let the others go again.
+                unsafe { Self {}.resume(&|_| None) };
+                return Err(HookError::Suspend);
+            }
+
+            thread::yield_now();
+        }
+
+        Ok(Self {})
+    }
+
+    /// Lets the parked threads continue after they moved themselves with `fixup`.
+    pub(crate) unsafe fn resume(self, fixup: Fixup<'_>) {
+        // SAFETY: The pointer is only used until all threads left the handler below.
+        let fixup: Fixup<'static> = unsafe { std::mem::transmute(fixup) };
+        FIXUP.store(
+            (&fixup as *const Fixup<'static>).cast_mut(),
+            Ordering::Release,
+        );
+        RELEASED.store(true, Ordering::Release);
+
+        while PARKED.load(Ordering::Acquire) != 0 {
+            thread::yield_now();
+        }
+
+        FIXUP.store(null_mut(), Ordering::Release);
+    }
+}
+
+/// Waits in the signal handler until the patch is applied.
+///
+/// Only async-signal-safe operations are allowed in here, which atomics and `sched_yield` are.
+#[cfg(target_os = "linux")]
+extern "C" fn park(_: c_int, _: *mut siginfo_t, context: *mut c_void) {
+    PARKED.fetch_add(1, Ordering::AcqRel);
+
+    // Yielding instead of spinning lets the patching thread run on a busy core.
+    while !RELEASED.load(Ordering::Acquire) {
+        unsafe { sched_yield() };
+    }
+
+    let fixup = FIXUP.load(Ordering::Acquire);
+    if !fixup.is_null() {
+        // SAFETY: The kernel passes the interrupted context as the third argument, and `resume`
+        // keeps the fixup alive until every thread left.
+        let context = unsafe { &mut *context.cast::<ucontext_t>() };
+        let rip = &mut context.uc_mcontext.gregs[REG_RIP as usize];
+        if let Some(moved) = unsafe { (*fixup)(*rip as usize) } {
+            *rip = moved as i64;
+        }
+    }
+
+    // The patched code may still be in this core's instruction pipeline, `cpuid` serializes it.
+    let _ = core::arch::x86_64::__cpuid(0);
+
+    PARKED.fetch_sub(1, Ordering::AcqRel);
+}
@@ -0,0 +1,167 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use super::threads::Stopped;
+use super::{Detour, Prologue, Write};
+use crate::ptr::FnPtr;
+use crate::Error;
+
+/// A batch of detours to enable or disable at once.
+///
+/// Other threads are stopped while the code is patched, and any thread that was about to run
+/// replaced instructions continues with their copy in the trampoline, so no thread ever sees a
+/// half-written jump.
+///
+/// # Examples
+/// ```no_run
+/// use transcend::ptr::{resolve_rva, Detour, Rva, Transaction};
+///
+/// type Update = unsafe extern "C" fn(f32);
+///
+/// unsafe extern "C" fn update(_: f32) {}
+/// unsafe extern "C" fn render(_: f32) {}
+///
+/// unsafe {
+///     let target = resolve_rva::<Update>(Rva::new(0x1000)).unwrap();
+///     let update = Detour::new_disabled(target, update).unwrap();
+///     let target = resolve_rva::<Update>(Rva::new(0x2000)).unwrap();
+///     let render = Detour::new_disabled(target, render).unwrap();
+///
+///     Transaction::begin()
+///         .enable(&update)
+///         .enable(&render)
+///         .commit()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Default)]
+pub struct Transaction<'a> {
+    operations: Vec<Operation<'a>>,
+}
+
+struct Operation<'a> {
+    target: usize,
+    prologue: &'a Prologue,
+    enabled: &'a Mutex<bool>,
+    enable: bool,
+}
+
+impl<'a> Transaction<'a> {
+    /// Starts an empty transaction.
+    #[must_use]
+    pub fn begin() -> Self {
+        Self::default()
+    }
+
+    /// Enables `detour` when the transaction is committed.
+    pub fn enable<F: FnPtr>(&mut self, detour: &'a Detour<F>) -> &mut Self {
+        self.push(detour, true)
+    }
+
+    /// Disables `detour` when the transaction is committed.
+    pub fn disable<F: FnPtr>(&mut self, detour: &'a Detour<F>) -> &mut Self {
+        self.push(detour, false)
+    }
+
+    fn push<F: FnPtr>(&mut self, detour: &'a Detour<F>, enable: bool) -> &mut Self {
+        // The last operation on a detour wins.
+        self.operations
+            .retain(|operation| !std::ptr::eq(operation.enabled, &detour.enabled));
+
+        self.operations.push(Operation {
+            target: detour.target,
+            prologue: &detour.prologue,
+            enabled: &detour.enabled,
+            enable,
+        });
+
+        self
+    }
+
+    /// Applies all operations while the other threads of the process are stopped.
+    ///
+    /// Either every operation is applied or, if any fails, none is.
+    ///
+    /// # Safety
+    /// Threads that are stopped within a detour or that return into replaced instructions are not
+    /// moved, their code has to stay valid.
+    pub unsafe fn commit(&mut self) -> Result<(), Error> {
+        // Only one transaction can stop the world at a time.
+        static COMMIT: Mutex<()> = Mutex::new(());
+        let _commit = COMMIT.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut states: Vec<MutexGuard<bool>> = self
+            .operations
+            .iter()
+            .map(|operation| {
+                operation
+                    .enabled
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+            })
+            .collect();
+
+        let pending: Vec<(usize, &Operation)> = self
+            .operations
+            .iter()
+            .enumerate()
+            .filter(|(index, operation)| *states[*index] != operation.enable)
+            .collect();
+
+        // Everything that allocates happens before other threads stop, one of them could be
+        // holding the allocator lock.
+        let mut writes = Vec::with_capacity(pending.len());
+        let mut undo = Vec::with_capacity(pending.len());
+        for (_, operation) in &pending {
+            let (new, old) = match operation.enable {
+                true => (&operation.prologue.patch, &operation.prologue.original),
+                false => (&operation.prologue.original, &operation.prologue.patch),
+            };
+
+            let target = operation.target as *mut u8;
+            writes.push(Write::new(target, new)?);
+            undo.push(Write::new(target, old)?);
+        }
+
+        let stopped = unsafe { Stopped::stop()? };
+
+        let mut result = Ok(());
+        for (index, write) in writes.iter().enumerate() {
+            if let Err(error) = unsafe { write.apply() } {
+                for write in undo[..index].iter().rev() {
+                    let _ = unsafe { write.apply() };
+                }
+
+                result = Err(error);
+                break;
+            }
+        }
+
+        // Threads about to run replaced instructions continue with their copy instead. Ones in
+        // restored code are already fine, the trampoline stays around.
+        let fixup = |rip: usize| {
+            if result.is_err() {
+                return None;
+            }
+
+            pending
+                .iter()
+                .filter(|(_, operation)| operation.enable)
+                .find_map(|(_, operation)| {
+                    let offset = rip.checked_sub(operation.target)?;
+                    let boundaries = &operation.prologue.boundaries;
+                    let index = boundaries.binary_search_by_key(&offset, |&(from, _)| from);
+                    index.ok().map(|index| boundaries[index].1)
+                })
+        };
+
+        unsafe { stopped.resume(&fixup) };
+
+        if result.is_ok() {
+            for (index, operation) in &pending {
+                *states[*index] = operation.enable;
+            }
+        }
+
+        result.map_err(Error::from)
+    }
+}
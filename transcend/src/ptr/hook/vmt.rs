@@ -0,0 +1,368 @@
+//! Hooks that replace entries of C++ virtual method tables instead of code.
+
+use core::fmt;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem::transmute_copy;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use super::entry::Entry;
+use super::HookError;
+use crate::ptr::{Address, FnPtr};
+use crate::Error;
+
+/// The entries in front of the address an object points to, which hold the RTTI complete object
+/// locator on MSVC and the offset to top and type info in the Itanium ABI.
+#[cfg(target_os = "windows")]
+const PREFIX: usize = 1;
+#[cfg(target_os = "linux")]
+const PREFIX: usize = 2;
+
+/// A virtual function whose entry in the vtable of its class points to a replacement.
+///
+/// Every object of the class calls the replacement, and the entry is restored when the hook is
+/// dropped.
+///
+/// # Examples
+/// ```no_run
+/// use std::sync::OnceLock;
+/// use transcend::ptr::{base, Rva, VmtHook};
+///
+/// type Tick = unsafe extern "C" fn(*mut u8, f32);
+///
+/// static TICK: OnceLock<VmtHook<Tick>> = OnceLock::new();
+///
+/// unsafe extern "C" fn tick(this: *mut u8, delta: f32) {
+///     // Run the game in slow motion.
+///     unsafe { (TICK.get().unwrap().original())(this, delta / 2.0) }
+/// }
+///
+/// unsafe {
+///     let vtable = base().unwrap() + Rva::new(0x1D3F10);
+///     TICK.get_or_init(|| VmtHook::new(vtable, 3, tick as Tick).unwrap());
+/// }
+/// ```
+pub struct VmtHook<F: FnPtr> {
+    entry: Entry,
+    function: PhantomData<F>,
+}
+
+impl<F: FnPtr> VmtHook<F> {
+    /// Points entry `index` of `vtable` to `replacement`, changing the protection of the table
+    /// while it is written.
+    ///
+    /// # Safety
+    /// `vtable` must point to a vtable with more than `index` entries, and the function at `index`
+    /// must have the signature `F`.
+    pub unsafe fn new(vtable: Address, index: usize, replacement: F) -> Result<Self, Error> {
+        // SAFETY: `FnPtr` is only implemented for function pointers.
+        let replacement = unsafe { transmute_copy::<F, usize>(&replacement) };
+        let entry =
+            unsafe { Entry::replace(vtable.as_mut_ptr::<usize>().add(index), replacement)? };
+
+        Ok(Self {
+            entry,
+            function: PhantomData,
+        })
+    }
+
+    /// Returns the function the entry pointed to before.
+    #[must_use]
+    pub fn original(&self) -> F {
+        // SAFETY: The caller of `new` guarantees the entry has the signature `F`.
+        unsafe { transmute_copy(&self.entry.original) }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for VmtHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VmtHook")
+            .field("entry", &(self.entry.address as *const usize))
+            .field("original", &(self.entry.original as *const u8))
+            .field("replacement", &(self.entry.replacement as *const u8))
+            .finish()
+    }
+}
+
+/// A private copy of the vtable of a single object, whose entries can be replaced without
+/// affecting any other object of the class.
+///
+/// Nothing is written to protected memory, only the vtable pointer of the object changes. It is
+/// pointed back to the original vtable when the shadow is dropped.
+///
+/// # Examples
+/// ```no_run
+/// use std::ffi::c_void;
+/// use std::sync::OnceLock;
+/// use transcend::ptr::ShadowVmt;
+///
+/// type Damage = unsafe extern "C" fn(*mut c_void, f32);
+///
+/// static PLAYER: OnceLock<ShadowVmt> = OnceLock::new();
+///
+/// unsafe extern "C" fn damage(this: *mut c_void, _: f32) {
+///     // The player ignores all damage, enemies of the same class do not.
+///     let original: Damage = PLAYER.get().unwrap().original(7).unwrap();
+///     unsafe { original(this, 0.0) }
+/// }
+///
+/// # let player: *mut c_void = std::ptr::null_mut();
+/// unsafe {
+///     let shadow = PLAYER.get_or_init(|| ShadowVmt::new(player, 32));
+///     shadow.hook(7, damage as Damage).unwrap();
+/// }
+/// ```
+pub struct ShadowVmt {
+    object: usize,
+    vtable: usize,
+    /// The copied prefix followed by the copied entries.
+    table: Box<[AtomicUsize]>,
+}
+
+impl ShadowVmt {
+    /// Copies the first `len` entries of the vtable of `object` and points the object to the copy.
+    ///
+    /// # Safety
+    /// `object` must point to a polymorphic C++ object whose vtable has at least `len` entries, and
+    /// it has to outlive the shadow.
+    pub unsafe fn new(object: *mut c_void, len: usize) -> Self {
+        let pointer = unsafe { &*object.cast::<AtomicPtr<usize>>() };
+        let vtable = pointer.load(Ordering::Acquire);
+
+        let table: Box<[AtomicUsize]> = (0..PREFIX + len)
+            .map(|index| AtomicUsize::new(unsafe { vtable.sub(PREFIX).add(index).read() }))
+            .collect();
+
+        pointer.store(
+            table[PREFIX..].as_ptr().cast_mut().cast(),
+            Ordering::Release,
+        );
+
+        Self {
+            object: object as usize,
+            vtable: vtable as usize,
+            table,
+        }
+    }
+
+    /// Points entry `index` of the copy to `replacement` and returns the function it pointed to
+    /// before.
+    ///
+    /// # Errors
+    /// [`HookError::Index`] if `index` is past the copied entries.
+    ///
+    /// # Safety
+    /// The function at `index` must have the signature `F`.
+    pub unsafe fn hook<F: FnPtr>(&self, index: usize, replacement: F) -> Result<F, Error> {
+        // SAFETY: `FnPtr` is only implemented for function pointers.
+        let replacement = unsafe { transmute_copy::<F, usize>(&replacement) };
+        let original = self.slot(index)?.swap(replacement, Ordering::AcqRel);
+
+        // SAFETY: The caller guarantees the entry has the signature `F`.
+        Ok(unsafe { transmute_copy(&original) })
+    }
+
+    /// Points entry `index` of the copy back to the function from the original vtable.
+    ///
+    /// # Errors
+    /// [`HookError::Index`] if `index` is past the copied entries.
+    pub fn unhook(&self, index: usize) -> Result<(), Error> {
+        self.slot(index)?
+            .store(self.entry(index)?, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns entry `index` of the original vtable.
+    ///
+    /// # Errors
+    /// [`HookError::Index`] if `index` is past the copied entries.
+    pub fn original<F: FnPtr>(&self, index: usize) -> Result<F, Error> {
+        let entry = self.entry(index)?;
+
+        // SAFETY: `FnPtr` is only implemented for function pointers.
+        Ok(unsafe { transmute_copy(&entry) })
+    }
+
+    /// Returns entry `index` of the copy.
+    fn slot(&self, index: usize) -> Result<&AtomicUsize, HookError> {
+        let len = self.table.len() - PREFIX;
+        self.table[PREFIX..]
+            .get(index)
+            .ok_or(HookError::Index { index, len })
+    }
+
+    /// Returns entry `index` of the original vtable.
+    fn entry(&self, index: usize) -> Result<usize, HookError> {
+        self.slot(index)?;
+
+        // SAFETY: The entry was copied from the original vtable, so it is readable.
+        Ok(unsafe { (self.vtable as *const usize).add(index).read() })
+    }
+}
+
+impl Drop for ShadowVmt {
+    fn drop(&mut self) {
+        // Leave the object alone if something else repointed it in the meantime.
+        let pointer = unsafe { &*(self.object as *const AtomicPtr<usize>) };
+        let _ = pointer.compare_exchange(
+            self.table[PREFIX..].as_ptr().cast_mut().cast(),
+            self.vtable as *mut usize,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+    }
+}
+
+impl fmt::Debug for ShadowVmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShadowVmt")
+            .field("object", &(self.object as *const c_void))
+            .field("vtable", &(self.vtable as *const usize))
+            .field("len", &(self.table.len() - PREFIX))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::UnsafeCell;
+    use std::ptr::read_volatile;
+
+    type Method = unsafe extern "C" fn(*const Object) -> u32;
+
+    /// A vtable the way a compiler lays it out, behind the entries in front of it.
+    struct Vtable(UnsafeCell<[Option<Method>; PREFIX + 3]>);
+
+    // SAFETY: The entries are only replaced through atomic stores or a hook.
+    unsafe impl Sync for Vtable {}
+
+    impl Vtable {
+        const fn new() -> Self {
+            let mut entries: [Option<Method>; PREFIX + 3] = [None; PREFIX + 3];
+            entries[PREFIX] = Some(first);
+            entries[PREFIX + 1] = Some(second);
+            entries[PREFIX + 2] = Some(third);
+            Self(UnsafeCell::new(entries))
+        }
+
+        fn address(&self) -> Address {
+            Address::from_ptr(unsafe { self.0.get().cast::<Option<Method>>().add(PREFIX) })
+        }
+    }
+
+    #[repr(C)]
+    struct Object {
+        vtable: *const Option<Method>,
+    }
+
+    impl Object {
+        fn new(vtable: &Vtable) -> Self {
+            Self {
+                vtable: vtable.address().as_ptr(),
+            }
+        }
+
+        /// Calls virtual function `index` the way compiled code does.
+        fn call(&self, index: usize) -> u32 {
+            let method = unsafe { read_volatile(read_volatile(&self.vtable).add(index)) };
+            unsafe { method.unwrap()(self) }
+        }
+
+        fn as_ptr(&mut self) -> *mut c_void {
+            (self as *mut Self).cast()
+        }
+    }
+
+    unsafe extern "C" fn first(_: *const Object) -> u32 {
+        1
+    }
+
+    unsafe extern "C" fn second(_: *const Object) -> u32 {
+        2
+    }
+
+    unsafe extern "C" fn third(_: *const Object) -> u32 {
+        3
+    }
+
+    unsafe extern "C" fn replacement(_: *const Object) -> u32 {
+        20
+    }
+
+    #[test]
+    fn vmt_hook() {
+        static VTABLE: Vtable = Vtable::new();
+        let (a, b) = (Object::new(&VTABLE), Object::new(&VTABLE));
+
+        let hook = unsafe { VmtHook::new(VTABLE.address(), 1, replacement as Method) }.unwrap();
+        assert_eq!(hook.original() as usize, second as Method as usize);
+
+        // Every object of the class sees the replacement.
+        assert_eq!((a.call(0), a.call(1), a.call(2)), (1, 20, 3));
+        assert_eq!(b.call(1), 20);
+
+        drop(hook);
+        assert_eq!((a.call(1), b.call(1)), (2, 2));
+    }
+
+    #[test]
+    fn shadow_vmt() {
+        static VTABLE: Vtable = Vtable::new();
+        let (mut a, b) = (Object::new(&VTABLE), Object::new(&VTABLE));
+
+        let shadow = unsafe { ShadowVmt::new(a.as_ptr(), 3) };
+        assert_ne!(a.vtable, b.vtable);
+        assert_eq!(a.call(1), 2);
+
+        // Only the shadowed object sees the replacement.
+        let original = unsafe { shadow.hook(1, replacement as Method) }.unwrap();
+        assert_eq!(original as usize, second as Method as usize);
+        assert_eq!((a.call(0), a.call(1), a.call(2)), (1, 20, 3));
+        assert_eq!(b.call(1), 2);
+
+        let original: Method = shadow.original(1).unwrap();
+        assert_eq!(original as usize, second as Method as usize);
+
+        shadow.unhook(1).unwrap();
+        assert_eq!(a.call(1), 2);
+
+        unsafe { shadow.hook(2, replacement as Method) }.unwrap();
+        assert_eq!(a.call(2), 20);
+
+        drop(shadow);
+        assert_eq!(a.vtable, b.vtable);
+        assert_eq!(a.call(2), 3);
+    }
+
+    #[test]
+    fn shadow_vmt_index() {
+        static VTABLE: Vtable = Vtable::new();
+        let mut object = Object::new(&VTABLE);
+
+        let shadow = unsafe { ShadowVmt::new(object.as_ptr(), 2) };
+        let error = Error::Hook(HookError::Index { index: 2, len: 2 });
+
+        assert_eq!(
+            unsafe { shadow.hook(2, replacement as Method) }.unwrap_err(),
+            error
+        );
+        assert_eq!(shadow.unhook(2).unwrap_err(), error);
+        assert_eq!(shadow.original::<Method>(2).unwrap_err(), error);
+        assert_eq!(object.call(1), 2);
+    }
+
+    #[test]
+    fn shadow_vmt_repointed() {
+        static VTABLE: Vtable = Vtable::new();
+        static OTHER: Vtable = Vtable::new();
+        let mut object = Object::new(&VTABLE);
+
+        // Something else took over the object after the shadow, it keeps its vtable.
+        let shadow = unsafe { ShadowVmt::new(object.as_ptr(), 3) };
+        object.vtable = OTHER.address().as_ptr();
+
+        drop(shadow);
+        assert_eq!(object.vtable, OTHER.address().as_ptr());
+    }
+}
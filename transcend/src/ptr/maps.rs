@@ -0,0 +1,39 @@
+//! The memory map of the current process.
+
+use libc::{c_int, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+use std::fs;
+use std::ops::Range;
+
+/// A mapped range of memory.
+#[derive(Debug, Clone)]
+pub(crate) struct Region {
+    pub(crate) range: Range<usize>,
+    /// The `PROT_*` flags of the range.
+    pub(crate) protection: c_int,
+}
+
+/// Returns the mapped regions of the current process, sorted by address.
+pub(crate) fn regions() -> Vec<Region> {
+    let Ok(maps) = fs::read_to_string("/proc/self/maps") else {
+        return Vec::new();
+    };
+
+    maps.lines().filter_map(parse).collect()
+}
+
+/// Parses a line like `7f1c2a000000-7f1c2a021000 r-xp 00000000 08:01 1234 /usr/lib/libc.so.6`.
+fn parse(line: &str) -> Option<Region> {
+    let mut fields = line.split_ascii_whitespace();
+    let (start, end) = fields.next()?.split_once('-')?;
+    let permissions = fields.next()?.as_bytes();
+
+    let flag = |index: usize, letter: u8, flag: c_int| match permissions.get(index) {
+        Some(&found) if found == letter => flag,
+        _ => PROT_NONE,
+    };
+
+    Some(Region {
+        range: usize::from_str_radix(start, 16).ok()?..usize::from_str_radix(end, 16).ok()?,
+        protection: flag(0, b'r', PROT_READ) | flag(1, b'w', PROT_WRITE) | flag(2, b'x', PROT_EXEC),
+    })
+}
@@ -0,0 +1,212 @@
+use super::{Address, FnPtr, Rva, Section};
+use crate::Error;
+use std::path::PathBuf;
+use std::slice::from_raw_parts;
+
+/// An executable image (the program itself, a DLL or a shared object) loaded into the current process.
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// The file name of the image, e.g. `kernel32.dll` or `libc.so.6`.
+    pub name: String,
+    /// The full path the image was loaded from.
+    pub path: PathBuf,
+    /// The lowest address of the mapped image, where its headers start.
+    pub base: Address,
+    /// The number of bytes from `base` to the end of the last mapped segment or section.
+    pub size: usize,
+}
+
+impl Module {
+    /// Returns the whole mapped image as a byte slice.
+    #[must_use]
+    pub fn as_slice(&self) -> &'static [u8] {
+        unsafe { from_raw_parts(self.base.as_ptr(), self.size) }
+    }
+
+    pub fn sections(&self) -> Result<Vec<Section>, Error> {
+        #[cfg(target_os = "windows")]
+        {
+            // SAFETY: `base` was handed out by the loader and the module is still loaded.
+            unsafe { super::pe::sections(self.base) }.ok_or(Error::InvalidHeader)
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            super::elf::sections(&self.path, self.base)
+        }
+
+        #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+        {
+            Err(Error::Unsupported)
+        }
+    }
+
+    /// Resolves an offset from the base address of this module to a function.
+    ///
+    /// # Safety
+    /// See [`resolve_rva`](super::resolve_rva).
+    #[must_use]
+    #[inline(always)]
+    pub unsafe fn resolve_rva<F: FnPtr>(&self, rva: Rva) -> F {
+        // SAFETY: The caller guarantees that `F` is an `unsafe extern "ABI" fn`.
+        unsafe { (self.base + rva).as_fn() }
+    }
+}
+
+/// Returns every module currently loaded into the process, starting with the program itself.
+///
+/// On Linux the vDSO is left out, the kernel maps it without a file behind it.
+pub fn modules() -> Result<Vec<Module>, Error> {
+    #[cfg(target_os = "windows")]
+    {
+        use std::ffi::OsString;
+        use std::mem::zeroed;
+        use std::os::windows::ffi::OsStringExt;
+        use windows::Win32::Foundation::HMODULE;
+        use windows::Win32::System::LibraryLoader::GetModuleFileNameW;
+        use windows::Win32::System::ProcessStatus::{
+            EnumProcessModules, GetModuleInformation, MODULEINFO,
+        };
+        use windows::Win32::System::Threading::GetCurrentProcess;
+
+        let process = unsafe { GetCurrentProcess() };
+
+        // Modules can be loaded between the two calls, so keep asking until the buffer was large enough.
+        let mut handles = Vec::<HMODULE>::new();
+        loop {
+            let capacity = (handles.len() * size_of::<HMODULE>()) as u32;
+            let mut needed = 0;
+
+            unsafe { EnumProcessModules(process, handles.as_mut_ptr(), capacity, &mut needed) }
+                .map_err(|_| Error::last_os_error())?;
+
+            if needed <= capacity {
+                handles.truncate(needed as usize / size_of::<HMODULE>());
+                break;
+            }
+
+            handles.resize(needed as usize / size_of::<HMODULE>(), HMODULE::default());
+        }
+
+        let modules = handles
+            .into_iter()
+            .filter_map(|handle| {
+                let mut info: MODULEINFO = unsafe { zeroed() };
+                unsafe {
+                    GetModuleInformation(process, handle, &mut info, size_of::<MODULEINFO>() as u32)
+                }
+                .ok()?;
+
+                let mut buffer = [0u16; 32767];
+                let len = unsafe { GetModuleFileNameW(handle, &mut buffer) } as usize;
+                let path = PathBuf::from(OsString::from_wide(&buffer[..len]));
+
+                Some(Module {
+                    name: file_name(&path),
+                    path,
+                    base: Address::from_ptr(info.lpBaseOfDll),
+                    size: info.SizeOfImage as usize,
+                })
+            })
+            .collect();
+
+        Ok(modules)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use super::elf::{image_end, image_start};
+        use libc::{
+            c_int, c_void, dl_iterate_phdr, dl_phdr_info, getauxval, size_t, AT_SYSINFO_EHDR,
+        };
+        use std::ffi::CStr;
+
+        unsafe extern "C" fn callback(
+            info: *mut dl_phdr_info,
+            _size: size_t,
+            data: *mut c_void,
+        ) -> c_int {
+            let modules = unsafe { &mut *(data as *mut Vec<Module>) };
+            let info = unsafe { &*info };
+
+            let bias = info.dlpi_addr as usize;
+            let headers = unsafe { from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize) };
+            let base = image_start(bias, headers);
+
+            // The vDSO is mapped by the kernel and has no file to read sections from.
+            if base == unsafe { getauxval(AT_SYSINFO_EHDR) } as usize {
+                return 0;
+            }
+
+            // The program itself is reported with an empty name.
+            let name = unsafe { CStr::from_ptr(info.dlpi_name) };
+            let path = match name.is_empty() {
+                true => std::env::current_exe().unwrap_or_default(),
+                false => PathBuf::from(name.to_string_lossy().into_owned()),
+            };
+
+            modules.push(Module {
+                name: file_name(&path),
+                path,
+                base: Address::new(base),
+                size: image_end(bias, headers) - base,
+            });
+
+            0
+        }
+
+        let mut modules = Vec::new();
+        unsafe {
+            dl_iterate_phdr(
+                Some(callback),
+                &mut modules as *mut Vec<Module> as *mut c_void,
+            )
+        };
+        Ok(modules)
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    {
+        Err(Error::Unsupported)
+    }
+}
+
+/// Looks up a loaded module by its file name, e.g. `module("engine.dll")`.
+///
+/// Names are compared case-insensitively on Windows, matching the loader.
+pub fn module(name: &str) -> Result<Module, Error> {
+    let module = modules()?.into_iter().find(|module| {
+        #[cfg(target_os = "windows")]
+        {
+            module.name.eq_ignore_ascii_case(name)
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            module.name == name
+        }
+    });
+
+    module.ok_or(Error::ModuleNotFound)
+}
+
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_vdso() {
+        let modules = modules().unwrap();
+        let vdso = unsafe { libc::getauxval(libc::AT_SYSINFO_EHDR) } as usize;
+
+        assert_eq!(modules[0].path, std::env::current_exe().unwrap());
+        assert!(modules.iter().all(|module| module.base.as_usize() != vdso));
+        assert!(modules.iter().all(|module| module.path.is_file()));
+    }
+}
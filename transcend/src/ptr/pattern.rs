@@ -0,0 +1,622 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::Error;
+
+/// A byte signature with an explicit wildcard mask.
+///
+/// A byte of the scanned memory matches when it equals the pattern byte in every bit set in the mask,
+/// so a `??` wildcard has a mask of `0x00`, a literal `FF` a mask of `0xFF` and a half-byte wildcard
+/// like `4?` a mask of `0xF0`.
+///
+/// A pattern can also carry a capture offset, so scanning for `48 8D 0D &?? ?? ?? ??` yields the
+/// address of the displacement rather than the start of the instruction.
+///
+/// Two patterns are equal when they match the same bytes and capture the same offset, whatever the
+/// bits under a wildcard hold.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    bytes: Cow<'static, [u8]>,
+    mask: Cow<'static, [u8]>,
+    offset: usize,
+}
+
+impl Pattern {
+    /// Creates a pattern from a byte and a mask array of the same length.
+    pub fn new(bytes: impl Into<Vec<u8>>, mask: impl Into<Vec<u8>>) -> Result<Self, Error> {
+        let (bytes, mask) = (bytes.into(), mask.into());
+
+        if bytes.len() != mask.len() {
+            return Err(ParsePatternError::LengthMismatch {
+                bytes: bytes.len(),
+                mask: mask.len(),
+            }
+            .into());
+        }
+
+        Ok(Self::owned(bytes, mask, 0))
+    }
+
+    /// Creates a pattern from static arrays and a capture offset, usable in `const` and `static`
+    /// items.
+    ///
+    /// This is what the `sig!` macro of the `macros` feature expands to, which checks the arguments
+    /// while it compiles.
+    ///
+    /// # Panics
+    /// Panics if `bytes` and `mask` differ in length or `offset` lies outside the pattern, at
+    /// compile time when used in a constant.
+    #[must_use]
+    pub const fn from_static(bytes: &'static [u8], mask: &'static [u8], offset: usize) -> Self {
+        assert!(
+            bytes.len() == mask.len(),
+            "pattern and mask differ in length"
+        );
+        assert!(
+            offset == 0 || offset < bytes.len(),
+            "capture offset outside the pattern"
+        );
+
+        Self {
+            bytes: Cow::Borrowed(bytes),
+            mask: Cow::Borrowed(mask),
+            offset,
+        }
+    }
+
+    /// Creates a pattern from a byte and a mask array already known to be of the same length.
+    fn owned(mut bytes: Vec<u8>, mask: Vec<u8>, offset: usize) -> Self {
+        // Bits outside the mask are never compared, clear them so `bytes` and the formatted pattern
+        // only show what matters.
+        bytes
+            .iter_mut()
+            .zip(&mask)
+            .for_each(|(byte, mask)| *byte &= mask);
+
+        Self {
+            bytes: Cow::Owned(bytes),
+            mask: Cow::Owned(mask),
+            offset,
+        }
+    }
+
+    /// Sets the capture offset, which scans add to the address of every match.
+    pub fn capture(mut self, offset: usize) -> Result<Self, Error> {
+        if offset >= self.len() {
+            return Err(ParsePatternError::CaptureOutOfRange {
+                offset,
+                len: self.len(),
+            }
+            .into());
+        }
+
+        self.offset = offset;
+        Ok(self)
+    }
+
+    /// Returns the capture offset, the position of the byte marked with `&`.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[must_use]
+    pub fn mask(&self) -> &[u8] {
+        &self.mask
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        match &self.bytes {
+            Cow::Borrowed(bytes) => bytes.len(),
+            Cow::Owned(bytes) => bytes.len(),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Checks whether `window` starts with this pattern.
+    #[must_use]
+    #[inline]
+    pub fn matches(&self, window: &[u8]) -> bool {
+        window.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(self.mask.iter())
+                .zip(window)
+                .all(|((byte, mask), value)| (value ^ byte) & mask == 0)
+    }
+}
+
+impl Pattern {
+    /// Returns the pattern bytes with every bit outside the mask cleared.
+    fn masked(&self) -> impl Iterator<Item = u8> + '_ {
+        self.bytes
+            .iter()
+            .zip(self.mask.iter())
+            .map(|(byte, mask)| byte & mask)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset && self.mask == other.mask && self.masked().eq(other.masked())
+    }
+}
+
+impl Eq for Pattern {}
+
+impl Hash for Pattern {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.masked().for_each(|byte| byte.hash(state));
+        self.mask.hash(state);
+        self.offset.hash(state);
+    }
+}
+
+impl From<&[u8]> for Pattern {
+    /// Creates a pattern without any wildcards.
+    fn from(bytes: &[u8]) -> Self {
+        Self::owned(bytes.to_vec(), vec![0xFF; bytes.len()], 0)
+    }
+}
+
+impl Pattern {
+    /// Formats the pattern in IDA style, e.g. `48 8B ? 4? &E8`.
+    #[must_use]
+    pub fn to_ida(&self) -> String {
+        self.format_bytes("?")
+    }
+
+    /// Formats the pattern in x64dbg style, e.g. `48 8B ?? 4? &E8`.
+    #[must_use]
+    pub fn to_x64dbg(&self) -> String {
+        self.format_bytes("??")
+    }
+
+    /// Formats the pattern in code style, e.g. `"\x48\x8B\x00\x40", "xx??"`.
+    ///
+    /// Code style masks only work on whole bytes, so half-byte wildcards widen to full wildcards, and
+    /// there is no way to express the capture offset.
+    #[must_use]
+    pub fn to_code(&self) -> String {
+        let bytes: String = self
+            .bytes
+            .iter()
+            .map(|byte| format!("\\x{byte:02X}"))
+            .collect();
+        let mask: String = self
+            .mask
+            .iter()
+            .map(|&mask| if mask == 0xFF { 'x' } else { '?' })
+            .collect();
+
+        format!("\"{bytes}\", \"{mask}\"")
+    }
+
+    fn format_bytes(&self, wildcard: &str) -> String {
+        let nibble = |value: u8, mask: u8| match mask {
+            0 => '?',
+            _ => b"0123456789ABCDEF"[value as usize] as char,
+        };
+
+        self.bytes
+            .iter()
+            .zip(self.mask.iter())
+            .map(|(&byte, &mask)| match mask {
+                0x00 => wildcard.to_owned(),
+                _ => [
+                    nibble(byte >> 4, mask >> 4 & 0xF),
+                    nibble(byte & 0xF, mask & 0xF),
+                ]
+                .iter()
+                .collect(),
+            })
+            .enumerate()
+            .map(|(index, byte)| match index == self.offset && index != 0 {
+                true => format!("&{byte}"),
+                false => byte,
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for Pattern {
+    /// Formats the pattern in x64dbg style, which round-trips through [`FromStr`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_x64dbg())
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = ParsePatternError;
+
+    /// Parses a signature in any of the common formats:
+    ///
+    /// - IDA style, where `?` is a wildcard byte: `48 8B ? ?`
+    /// - x64dbg style, where `??` is a wildcard byte: `48 8B ?? ??` or `488B????`
+    /// - code style, a byte string and a mask of `x` and `?`: `"\x48\x8B\x00\x00", "xx??"`
+    ///
+    /// In the first two `?` may also replace a single nibble, as in `4? 8B ?5`, and a `&` in front of a
+    /// byte marks it as the [capture offset](Self::offset), as in `E8 &?? ?? ?? ??`.
+    ///
+    /// # Examples
+    /// ```
+    /// use transcend::ptr::Pattern;
+    ///
+    /// let ida: Pattern = "48 8B ? ?".parse().unwrap();
+    /// let x64dbg: Pattern = "48 8B ?? ??".parse().unwrap();
+    /// let code: Pattern = r#""\x48\x8B\x00\x00", "xx??""#.parse().unwrap();
+    ///
+    /// assert_eq!(ida, x64dbg);
+    /// assert_eq!(ida, code);
+    /// assert_eq!(ida, ida.to_code().parse().unwrap());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (bytes, mask, offset) = match s.contains("\\x") {
+            true => parse_code(s).map(|(bytes, mask)| (bytes, mask, 0))?,
+            false => parse_hex(s)?,
+        };
+
+        if bytes.is_empty() {
+            return Err(ParsePatternError::Empty);
+        }
+
+        Ok(Self::owned(bytes, mask, offset))
+    }
+}
+
+/// Parses whitespace separated IDA or x64dbg style bytes.
+fn parse_hex(s: &str) -> Result<(Vec<u8>, Vec<u8>, usize), ParsePatternError> {
+    let mut bytes = Vec::new();
+    let mut mask = Vec::new();
+    let mut capture: Option<(usize, usize)> = None;
+
+    for token in s.split_ascii_whitespace() {
+        // A `&` marks the following byte, either glued to it or standing on its own.
+        let token = match token.strip_prefix('&') {
+            Some(rest) => {
+                let position = position(s, token);
+
+                if capture.is_some() {
+                    return Err(ParsePatternError::DuplicateCapture { position });
+                }
+
+                capture = Some((bytes.len(), position));
+
+                match rest.is_empty() {
+                    true => continue,
+                    false => rest,
+                }
+            }
+            None => token,
+        };
+
+        let invalid = || ParsePatternError::InvalidByte {
+            token: token.to_owned(),
+            position: position(s, token),
+        };
+
+        if token == "?" || token == "??" {
+            bytes.push(0x00);
+            mask.push(0x00);
+            continue;
+        }
+
+        if token.len() % 2 != 0 || !token.is_ascii() {
+            return Err(invalid());
+        }
+
+        // x64dbg also accepts runs of bytes without spaces in between.
+        for pair in token.as_bytes().chunks(2) {
+            let (value, nibbles) =
+                parse_byte(pair[0] as char, pair[1] as char).ok_or_else(invalid)?;
+            bytes.push(value);
+            mask.push(nibbles);
+        }
+    }
+
+    match capture {
+        Some((offset, position)) if offset == bytes.len() => {
+            Err(ParsePatternError::DanglingCapture { position })
+        }
+        Some((offset, _)) => Ok((bytes, mask, offset)),
+        None => Ok((bytes, mask, 0)),
+    }
+}
+
+/// Parses a code style byte string and mask, optionally quoted and separated by a comma.
+fn parse_code(s: &str) -> Result<(Vec<u8>, Vec<u8>), ParsePatternError> {
+    fn unquote(part: &str) -> &str {
+        part.trim().trim_matches('"')
+    }
+
+    let (literal, pattern) = s.split_once(',').ok_or(ParsePatternError::MissingMask)?;
+    let (literal, pattern) = (unquote(literal), unquote(pattern));
+
+    let mut bytes = Vec::new();
+    let mut rest = literal;
+    while !rest.is_empty() {
+        let invalid = || ParsePatternError::InvalidEscape {
+            position: position(s, rest),
+        };
+
+        let digits = rest.strip_prefix("\\x").ok_or_else(invalid)?;
+        let byte = digits
+            .get(..2)
+            .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(invalid)?;
+
+        bytes.push(u8::from_str_radix(byte, 16).map_err(|_| invalid())?);
+        rest = &digits[2..];
+    }
+
+    let mask = pattern
+        .char_indices()
+        .map(|(index, c)| match c {
+            'x' | 'X' => Ok(0xFF),
+            '?' => Ok(0x00),
+            found => Err(ParsePatternError::InvalidMask {
+                found,
+                position: position(s, pattern) + index,
+            }),
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    if bytes.len() != mask.len() {
+        return Err(ParsePatternError::LengthMismatch {
+            bytes: bytes.len(),
+            mask: mask.len(),
+        });
+    }
+
+    Ok((bytes, mask))
+}
+
+/// Parses a single byte like `8B`, `4?` or `?5` into its value and mask.
+fn parse_byte(high: char, low: char) -> Option<(u8, u8)> {
+    let nibble = |c: char| match c {
+        '?' => Some((0x0, 0x0)),
+        c => c.to_digit(16).map(|digit| (digit as u8, 0xF)),
+    };
+
+    let (high, high_mask) = nibble(high)?;
+    let (low, low_mask) = nibble(low)?;
+    Some((high << 4 | low, high_mask << 4 | low_mask))
+}
+
+/// Returns the byte offset of `part` inside `s`, which it must be a subslice of.
+fn position(s: &str, part: &str) -> usize {
+    part.as_ptr() as usize - s.as_ptr() as usize
+}
+
+/// An error returned when parsing a [`Pattern`] from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePatternError {
+    /// The string contains no bytes.
+    Empty,
+    /// A token is neither a pair of hex digits nor a wildcard.
+    InvalidByte { token: String, position: usize },
+    /// A code style byte string contains something other than `\xHH` escapes.
+    InvalidEscape { position: usize },
+    /// A code style mask contains something other than `x` and `?`.
+    InvalidMask { found: char, position: usize },
+    /// A code style byte string is missing its mask.
+    MissingMask,
+    /// A code style byte string and its mask differ in length.
+    LengthMismatch { bytes: usize, mask: usize },
+    /// More than one byte is marked with `&`.
+    DuplicateCapture { position: usize },
+    /// A `&` marker is not followed by any byte.
+    DanglingCapture { position: usize },
+    /// The capture offset lies past the last of the `len` bytes of the pattern.
+    CaptureOutOfRange { offset: usize, len: usize },
+}
+
+impl fmt::Display for ParsePatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "pattern is empty"),
+            Self::InvalidByte { token, position } => write!(
+                f,
+                "invalid byte `{token}` at position {position}, expected hex digits or `?`"
+            ),
+            Self::InvalidEscape { position } => {
+                write!(f, "expected a `\\xHH` escape at position {position}")
+            }
+            Self::InvalidMask { found, position } => write!(
+                f,
+                "invalid mask character `{found}` at position {position}, expected `x` or `?`"
+            ),
+            Self::MissingMask => write!(
+                f,
+                "byte string is missing its mask, expected `\"\\x..\", \"x?..\"`"
+            ),
+            Self::LengthMismatch { bytes, mask } => write!(
+                f,
+                "byte string has {bytes} bytes but its mask has {mask} characters"
+            ),
+            Self::DuplicateCapture { position } => write!(
+                f,
+                "second capture marker `&` at position {position}, only one byte can be captured"
+            ),
+            Self::DanglingCapture { position } => write!(
+                f,
+                "capture marker `&` at position {position} is not followed by a byte"
+            ),
+            Self::CaptureOutOfRange { offset, len } => write!(
+                f,
+                "capture offset {offset} lies outside the pattern of {len} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParsePatternError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash(pattern: &Pattern) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        pattern.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn literal_ff_is_not_a_wildcard() {
+        let pattern: Pattern = "FF 15 ??".parse().unwrap();
+
+        assert_eq!(pattern.bytes(), [0xFF, 0x15, 0x00]);
+        assert_eq!(pattern.mask(), [0xFF, 0xFF, 0x00]);
+        assert!(pattern.matches(&[0xFF, 0x15, 0x42]));
+        assert!(!pattern.matches(&[0xE8, 0x15, 0x42]));
+        assert!(!pattern.matches(&[0xFF, 0x15]));
+    }
+
+    #[test]
+    fn equality_ignores_wildcard_bits() {
+        static BYTES: [u8; 3] = [0x48, 0xFF, 0x4F];
+        static MASK: [u8; 3] = [0xFF, 0x00, 0xF0];
+
+        let borrowed = Pattern::from_static(&BYTES, &MASK, 0);
+        let owned = Pattern::new([0x48, 0x00, 0x40], MASK).unwrap();
+
+        assert_eq!(borrowed, owned);
+        assert_eq!(hash(&borrowed), hash(&owned));
+        assert_ne!(borrowed, Pattern::new([0x48, 0x00, 0x50], MASK).unwrap());
+        assert_ne!(owned, owned.clone().capture(2).unwrap());
+    }
+
+    #[test]
+    fn nibble_wildcards() {
+        let pattern: Pattern = "4? 8B ?5".parse().unwrap();
+
+        assert_eq!(pattern.bytes(), [0x40, 0x8B, 0x05]);
+        assert_eq!(pattern.mask(), [0xF0, 0xFF, 0x0F]);
+        assert!(pattern.matches(&[0x48, 0x8B, 0x05]));
+        assert!(pattern.matches(&[0x4C, 0x8B, 0x45]));
+        assert!(!pattern.matches(&[0x58, 0x8B, 0x05]));
+        assert!(!pattern.matches(&[0x48, 0x8B, 0x06]));
+
+        assert_eq!(pattern.to_x64dbg(), "4? 8B ?5");
+        assert_eq!(pattern.to_ida(), "4? 8B ?5");
+        // Code style can only express whole bytes.
+        assert_eq!(pattern.to_code(), r#""\x40\x8B\x05", "?x?""#);
+    }
+
+    #[test]
+    fn round_trips() {
+        for text in [
+            "48 8B 05 ?? ?? ?? ??",
+            "FF 15 ?? ?? ?? ?? 85 C0",
+            "E8 &?? ?? ?? ??",
+            "4? 8B ?5",
+        ] {
+            let pattern: Pattern = text.parse().unwrap();
+
+            assert_eq!(pattern.to_string(), text);
+            assert_eq!(pattern.to_ida().parse::<Pattern>().unwrap(), pattern);
+            assert_eq!(pattern.to_x64dbg().parse::<Pattern>().unwrap(), pattern);
+        }
+
+        let pattern: Pattern = "48 8B ? ? C3".parse().unwrap();
+        assert_eq!(pattern.to_ida(), "48 8B ? ? C3");
+        assert_eq!(pattern.to_code(), r#""\x48\x8B\x00\x00\xC3", "xx??x""#);
+        assert_eq!(pattern.to_code().parse::<Pattern>().unwrap(), pattern);
+        assert_eq!("488B????C3".parse::<Pattern>().unwrap(), pattern);
+    }
+
+    #[test]
+    fn capture() {
+        let pattern: Pattern = "48 8D 0D &?? ?? ?? ??".parse().unwrap();
+        assert_eq!(pattern.offset(), 3);
+        assert_eq!(pattern.to_ida(), "48 8D 0D &? ? ? ?");
+
+        // The marker may also stand on its own.
+        assert_eq!(
+            "48 8D 0D & ?? ?? ?? ??".parse::<Pattern>().unwrap(),
+            pattern
+        );
+
+        // Capturing the first byte is the same as capturing nothing.
+        let pattern: Pattern = "&E8 ?? ?? ?? ??".parse().unwrap();
+        assert_eq!(pattern.offset(), 0);
+        assert_eq!(pattern.to_string(), "E8 ?? ?? ?? ??");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |s: &str| s.parse::<Pattern>().unwrap_err();
+
+        assert_eq!(error(""), ParsePatternError::Empty);
+        assert_eq!(error("  "), ParsePatternError::Empty);
+        assert_eq!(
+            error("48 GZ"),
+            ParsePatternError::InvalidByte {
+                token: "GZ".to_owned(),
+                position: 3
+            }
+        );
+        assert_eq!(
+            error("48  8B5"),
+            ParsePatternError::InvalidByte {
+                token: "8B5".to_owned(),
+                position: 4
+            }
+        );
+        assert_eq!(
+            error(r#""\x48\x8G", "xx""#),
+            ParsePatternError::InvalidEscape { position: 5 }
+        );
+        assert_eq!(
+            error(r#""\x48\x8B", "xy""#),
+            ParsePatternError::InvalidMask {
+                found: 'y',
+                position: 14
+            }
+        );
+        assert_eq!(error(r#""\x48\x8B""#), ParsePatternError::MissingMask);
+        assert_eq!(
+            error(r#""\x48\x8B", "x""#),
+            ParsePatternError::LengthMismatch { bytes: 2, mask: 1 }
+        );
+        assert_eq!(
+            error("&48 &8B"),
+            ParsePatternError::DuplicateCapture { position: 4 }
+        );
+        assert_eq!(
+            error("48 8B &"),
+            ParsePatternError::DanglingCapture { position: 6 }
+        );
+    }
+
+    #[test]
+    fn new_errors() {
+        assert_eq!(
+            Pattern::new([0x48], [0xFF, 0xFF]).unwrap_err(),
+            Error::InvalidPattern(ParsePatternError::LengthMismatch { bytes: 1, mask: 2 })
+        );
+        assert_eq!(
+            Pattern::new([0x48], [0xFF])
+                .unwrap()
+                .capture(1)
+                .unwrap_err(),
+            Error::InvalidPattern(ParsePatternError::CaptureOutOfRange { offset: 1, len: 1 })
+        );
+    }
+}
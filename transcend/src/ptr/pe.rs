@@ -0,0 +1,284 @@
+//! Helpers for reading PE images, mapped by the loader or straight from a file.
+//!
+//! Headers are read byte by byte instead of through the Win32 structures, so files can be parsed
+//! on any platform.
+
+#[cfg(target_os = "windows")]
+use super::{Address, Section};
+#[cfg(target_os = "windows")]
+use std::slice::from_raw_parts;
+
+use std::ffi::CStr;
+
+use super::Rva;
+use crate::Error;
+
+const DOS_MAGIC: &[u8] = b"MZ";
+const NT_SIGNATURE: &[u8] = b"PE\0\0";
+const PE32_MAGIC: u16 = 0x10B;
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+const IMPORT_DIRECTORY: usize = 1;
+const SECTION_HEADER_LEN: usize = 40;
+const IMPORT_DESCRIPTOR_LEN: usize = 20;
+
+/// A function one PE image imports from another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// The file name of the module the function is imported from, e.g. `KERNEL32.dll`.
+    pub module: String,
+    /// The name of the function, unless it is imported by ordinal.
+    pub name: Option<String>,
+    /// The ordinal of the function, if it is imported by ordinal.
+    pub ordinal: Option<u16>,
+    /// The RVA of the entry in the import address table which the loader fills with the address
+    /// of the function.
+    pub iat: Rva,
+}
+
+/// Reads the import directory of a PE file, e.g. one read from disk.
+pub fn imports(file: &[u8]) -> Result<Vec<Import>, Error> {
+    Image::parse(file, Layout::File)
+        .and_then(|image| image.imports())
+        .ok_or(Error::InvalidHeader)
+}
+
+/// Reads the import directory of the image mapped at `base`.
+///
+/// # Safety
+/// `base` must point to the start of a PE image mapped by the loader.
+#[cfg(target_os = "windows")]
+pub(crate) unsafe fn mapped_imports(base: Address) -> Vec<Import> {
+    unsafe { Image::mapped(base) }
+        .and_then(|image| image.imports())
+        .unwrap_or_default()
+}
+
+/// Reads the section table of the image mapped at `base`.
+///
+/// # Safety
+/// `base` must point to the start of a PE image mapped by the loader.
+#[cfg(target_os = "windows")]
+pub(crate) unsafe fn sections(base: Address) -> Option<Vec<Section>> {
+    let image = unsafe { Image::mapped(base) }?;
+
+    let sections = image
+        .sections
+        .iter()
+        .map(|section| Section {
+            name: section.name.clone(),
+            base: base + Rva::new(section.virtual_address),
+            len: section.virtual_size,
+        })
+        .collect();
+
+    Some(sections)
+}
+
+/// Where the data of an RVA is found in the bytes of an image.
+#[derive(Clone, Copy)]
+enum Layout {
+    /// Sections are stored at their file offsets.
+    File,
+    /// Sections are placed at their RVAs, the way the loader maps them.
+    #[cfg(target_os = "windows")]
+    Mapped,
+}
+
+struct SectionHeader {
+    #[cfg(target_os = "windows")]
+    name: String,
+    virtual_size: usize,
+    virtual_address: usize,
+    raw_size: usize,
+    raw_offset: usize,
+}
+
+struct Image<'a> {
+    bytes: &'a [u8],
+    layout: Layout,
+    /// PE32+ images use 64-bit thunks, PE32 images 32-bit ones.
+    wide: bool,
+    headers_len: usize,
+    import_directory: Option<usize>,
+    sections: Vec<SectionHeader>,
+}
+
+impl<'a> Image<'a> {
+    /// # Safety
+    /// `base` must point to the start of a PE image mapped by the loader.
+    #[cfg(target_os = "windows")]
+    unsafe fn mapped(base: Address) -> Option<Image<'static>> {
+        let base = base.as_ptr::<u8>();
+
+        // The size of the image is in the headers, which are at least as large as the DOS header.
+        let dos_header = unsafe { from_raw_parts(base, 0x40) };
+        let optional_header = u32(dos_header, 0x3C)? as usize + 24;
+        let headers = unsafe { from_raw_parts(base, optional_header + 60) };
+        let len = u32(headers, optional_header + 56)? as usize;
+
+        Image::parse(unsafe { from_raw_parts(base, len) }, Layout::Mapped)
+    }
+
+    fn parse(bytes: &'a [u8], layout: Layout) -> Option<Self> {
+        if bytes.get(..2)? != DOS_MAGIC {
+            return None;
+        }
+
+        let nt_headers = u32(bytes, 0x3C)? as usize;
+        if bytes.get(nt_headers..nt_headers + 4)? != NT_SIGNATURE {
+            return None;
+        }
+
+        let file_header = nt_headers + 4;
+        let section_count = u16(bytes, file_header + 2)? as usize;
+        let optional_header_len = u16(bytes, file_header + 16)? as usize;
+
+        let optional_header = file_header + 20;
+        let (wide, directories) = match u16(bytes, optional_header)? {
+            PE32_MAGIC => (false, optional_header + 92),
+            PE32_PLUS_MAGIC => (true, optional_header + 108),
+            _ => return None,
+        };
+
+        let headers_len = u32(bytes, optional_header + 60)? as usize;
+
+        let directory_count = u32(bytes, directories)? as usize;
+        let import_directory = match IMPORT_DIRECTORY < directory_count {
+            true => Some(u32(bytes, directories + 4 + IMPORT_DIRECTORY * 8)? as usize)
+                .filter(|&rva| rva != 0),
+            false => None,
+        };
+
+        let section_table = optional_header + optional_header_len;
+        let sections = (0..section_count)
+            .map(|index| {
+                let header = section_table + index * SECTION_HEADER_LEN;
+                Some(SectionHeader {
+                    #[cfg(target_os = "windows")]
+                    name: {
+                        let name = bytes.get(header..header + 8)?;
+                        let name = name.split(|&byte| byte == 0).next().unwrap_or(name);
+                        String::from_utf8_lossy(name).into_owned()
+                    },
+                    virtual_size: u32(bytes, header + 8)? as usize,
+                    virtual_address: u32(bytes, header + 12)? as usize,
+                    raw_size: u32(bytes, header + 16)? as usize,
+                    raw_offset: u32(bytes, header + 20)? as usize,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            bytes,
+            layout,
+            wide,
+            headers_len,
+            import_directory,
+            sections,
+        })
+    }
+
+    /// Returns the offset into the bytes of the image at which `rva` is stored.
+    fn offset(&self, rva: usize) -> Option<usize> {
+        match self.layout {
+            #[cfg(target_os = "windows")]
+            Layout::Mapped => Some(rva),
+            Layout::File if rva < self.headers_len => Some(rva),
+            Layout::File => self.sections.iter().find_map(|section| {
+                let offset = rva.checked_sub(section.virtual_address)?;
+                (offset < section.raw_size.min(section.virtual_size))
+                    .then_some(section.raw_offset + offset)
+            }),
+        }
+    }
+
+    fn u32(&self, rva: usize) -> Option<u32> {
+        u32(self.bytes, self.offset(rva)?)
+    }
+
+    fn thunk(&self, rva: usize) -> Option<u64> {
+        match self.wide {
+            true => u64(self.bytes, self.offset(rva)?),
+            false => self.u32(rva).map(u64::from),
+        }
+    }
+
+    fn string(&self, rva: usize) -> Option<String> {
+        let bytes = self.bytes.get(self.offset(rva)?..)?;
+        let string = CStr::from_bytes_until_nul(bytes).ok()?;
+        Some(string.to_string_lossy().into_owned())
+    }
+
+    fn imports(&self) -> Option<Vec<Import>> {
+        let Some(directory) = self.import_directory else {
+            return Some(Vec::new());
+        };
+
+        let (thunk_len, ordinal_flag) = match self.wide {
+            true => (8, 1 << 63),
+            false => (4, 1 << 31),
+        };
+
+        let mut imports = Vec::new();
+
+        // The directory ends with a descriptor of zeroes.
+        for descriptor in (directory..).step_by(IMPORT_DESCRIPTOR_LEN) {
+            let lookup = self.u32(descriptor)? as usize;
+            let name = self.u32(descriptor + 12)? as usize;
+            let iat = self.u32(descriptor + 16)? as usize;
+
+            if name == 0 && iat == 0 {
+                break;
+            }
+
+            let module = self.string(name)?;
+
+            // Once loaded the import address table holds addresses, only the lookup table still
+            // names the functions. Old linkers leave it out, files then name them in the IAT.
+            let names = match lookup {
+                0 => iat,
+                lookup => lookup,
+            };
+
+            for index in 0.. {
+                let thunk = self.thunk(names + index * thunk_len)?;
+                if thunk == 0 {
+                    break;
+                }
+
+                let (name, ordinal) = match thunk & ordinal_flag {
+                    // A hint precedes the name.
+                    0 => (Some(self.string((thunk & 0x7FFF_FFFF) as usize + 2)?), None),
+                    _ => (None, Some(thunk as u16)),
+                };
+
+                imports.push(Import {
+                    module: module.clone(),
+                    name,
+                    ordinal,
+                    iat: Rva::new(iat + index * thunk_len),
+                });
+            }
+        }
+
+        Some(imports)
+    }
+}
+
+fn u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
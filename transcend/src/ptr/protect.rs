@@ -0,0 +1,304 @@
+//! Changing the protection of memory for as long as a guard lives.
+
+use core::fmt;
+use std::io;
+
+use super::Address;
+use crate::Error;
+
+#[cfg(target_os = "windows")]
+use windows::Win32::System::Memory::{
+    VirtualProtect, VirtualQuery, MEMORY_BASIC_INFORMATION, MEM_COMMIT, PAGE_EXECUTE,
+    PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_NOACCESS,
+    PAGE_PROTECTION_FLAGS, PAGE_READONLY, PAGE_READWRITE, PAGE_WRITECOPY,
+};
+
+#[cfg(target_os = "linux")]
+use {
+    super::{elf::page_size, maps},
+    libc::{c_int, mprotect, ENOMEM, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE},
+};
+
+/// The protection flags of the platform.
+#[cfg(target_os = "windows")]
+pub(crate) type Native = PAGE_PROTECTION_FLAGS;
+#[cfg(target_os = "linux")]
+pub(crate) type Native = c_int;
+
+/// What may be done with a range of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protection {
+    NoAccess,
+    Read,
+    ReadWrite,
+    Execute,
+    ReadExecute,
+    ReadWriteExecute,
+}
+
+impl Protection {
+    #[cfg(target_os = "windows")]
+    pub(crate) fn native(self) -> Native {
+        match self {
+            Self::NoAccess => PAGE_NOACCESS,
+            Self::Read => PAGE_READONLY,
+            Self::ReadWrite => PAGE_READWRITE,
+            Self::Execute => PAGE_EXECUTE,
+            Self::ReadExecute => PAGE_EXECUTE_READ,
+            Self::ReadWriteExecute => PAGE_EXECUTE_READWRITE,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn native(self) -> Native {
+        match self {
+            Self::NoAccess => PROT_NONE,
+            Self::Read => PROT_READ,
+            Self::ReadWrite => PROT_READ | PROT_WRITE,
+            Self::Execute => PROT_EXEC,
+            Self::ReadExecute => PROT_READ | PROT_EXEC,
+            Self::ReadWriteExecute => PROT_READ | PROT_WRITE | PROT_EXEC,
+        }
+    }
+}
+
+/// A range of memory whose pages share one protection.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Region {
+    pub(crate) start: usize,
+    pub(crate) len: usize,
+    pub(crate) protection: Native,
+}
+
+/// Changes the protection of memory and restores the previous protection when it is dropped.
+///
+/// # Examples
+/// ```no_run
+/// use transcend::ptr::{base, ProtectGuard, Protection, Rva};
+///
+/// # fn main() -> Result<(), transcend::Error> {
+/// unsafe {
+///     // The name of the game is in `.rdata`.
+///     let title = base()? + Rva::new(0x1F2D40);
+///     let _guard = ProtectGuard::new(title, 4, Protection::ReadWrite)?;
+///     title.as_mut_ptr::<u8>().copy_from(b"Mod!".as_ptr(), 4);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProtectGuard {
+    /// The protection to restore, which can differ from page to page.
+    regions: Vec<Region>,
+}
+
+impl ProtectGuard {
+    /// Changes the protection of the pages that hold the `len` bytes at `address` to `protection`.
+    ///
+    /// # Safety
+    /// Nothing that relies on the current protection of the pages, like code running from them
+    /// when they are no longer executable, may use them until the guard is dropped.
+    pub unsafe fn new(address: Address, len: usize, protection: Protection) -> Result<Self, Error> {
+        let regions = regions(address.as_usize(), len)?;
+        unsafe { protect(address.as_usize(), len, protection.native())? };
+
+        Ok(Self { regions })
+    }
+
+    /// Restores the previous protection, unlike dropping the guard reporting whether it worked.
+    pub fn restore(mut self) -> Result<(), Error> {
+        self.reset()
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        std::mem::take(&mut self.regions)
+            .into_iter()
+            .try_for_each(|region| unsafe { protect(region.start, region.len, region.protection) })
+    }
+}
+
+impl Drop for ProtectGuard {
+    fn drop(&mut self) {
+        let _ = self.reset();
+    }
+}
+
+impl fmt::Debug for ProtectGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let start = self.regions.first().map_or(0, |region| region.start);
+        let end = self
+            .regions
+            .last()
+            .map_or(0, |region| region.start + region.len);
+
+        f.debug_struct("ProtectGuard")
+            .field("start", &(start as *const u8))
+            .field("end", &(end as *const u8))
+            .finish()
+    }
+}
+
+/// Returns the current protection of the `len` bytes at `address`, split where it changes.
+#[cfg(target_os = "windows")]
+pub(crate) fn regions(address: usize, len: usize) -> Result<Vec<Region>, Error> {
+    let end = address + len.max(1);
+    let mut regions = Vec::new();
+
+    let mut start = address;
+    while start < end {
+        let mut region = MEMORY_BASIC_INFORMATION::default();
+        let written = unsafe {
+            VirtualQuery(
+                Some(start as *const _),
+                &mut region,
+                size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+
+        if written == 0 {
+            return Err(failed());
+        }
+
+        let next = (region.BaseAddress as usize + region.RegionSize).min(end);
+        regions.push(Region {
+            start,
+            len: next - start,
+            protection: region.Protect,
+        });
+
+        start = next;
+    }
+
+    Ok(regions)
+}
+
+/// Returns the current protection of the pages that hold the `len` bytes at `address`, split where
+/// it changes.
+#[cfg(target_os = "linux")]
+pub(crate) fn regions(address: usize, len: usize) -> Result<Vec<Region>, Error> {
+    let page_size = page_size();
+    let end = (address + len.max(1)).next_multiple_of(page_size);
+    let mut regions = Vec::new();
+
+    let mut start = address & !(page_size - 1);
+    for region in maps::regions() {
+        if start >= end {
+            break;
+        }
+
+        if !region.range.contains(&start) {
+            continue;
+        }
+
+        let next = region.range.end.min(end);
+        regions.push(Region {
+            start,
+            len: next - start,
+            protection: region.protection,
+        });
+
+        start = next;
+    }
+
+    // Part of the range is not mapped, which `mprotect` fails on as well.
+    match start >= end {
+        true => Ok(regions),
+        false => Err(Error::Protect { code: ENOMEM }),
+    }
+}
+
+/// Returns how many of the `len` bytes at `address` can be read before the first page that can not.
+#[cfg(target_os = "windows")]
+pub(crate) fn readable(address: usize, len: usize) -> usize {
+    let readable = PAGE_READONLY
+        | PAGE_READWRITE
+        | PAGE_WRITECOPY
+        | PAGE_EXECUTE_READ
+        | PAGE_EXECUTE_READWRITE
+        | PAGE_EXECUTE_WRITECOPY;
+
+    let end = address.saturating_add(len);
+    let mut start = address;
+    while start < end {
+        let mut region = MEMORY_BASIC_INFORMATION::default();
+        let written = unsafe {
+            VirtualQuery(
+                Some(start as *const _),
+                &mut region,
+                size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+
+        if written == 0
+            || region.State != MEM_COMMIT
+            || region.Protect.0 & readable.0 == 0
+            || region.Protect.0 & PAGE_GUARD.0 != 0
+        {
+            break;
+        }
+
+        start = region.BaseAddress as usize + region.RegionSize;
+    }
+
+    start.min(end) - address
+}
+
+/// Returns how many of the `len` bytes at `address` can be read before the first page that can not.
+#[cfg(target_os = "linux")]
+pub(crate) fn readable(address: usize, len: usize) -> usize {
+    let end = address.saturating_add(len);
+    let mut start = address;
+
+    for region in maps::regions() {
+        if start >= end {
+            break;
+        }
+
+        if !region.range.contains(&start) {
+            continue;
+        }
+
+        if region.protection & PROT_READ == 0 {
+            break;
+        }
+
+        start = region.range.end;
+    }
+
+    start.min(end) - address
+}
+
+/// Changes the protection of the pages that hold the `len` bytes at `address`.
+///
+/// # Safety
+/// See [`ProtectGuard::new`].
+#[cfg(target_os = "windows")]
+pub(crate) unsafe fn protect(address: usize, len: usize, protection: Native) -> Result<(), Error> {
+    let mut previous = PAGE_PROTECTION_FLAGS(0);
+    unsafe { VirtualProtect(address as *const _, len, protection, &mut previous) }
+        .map_err(|_| failed())
+}
+
+/// Changes the protection of the pages that hold the `len` bytes at `address`.
+///
+/// # Safety
+/// See [`ProtectGuard::new`].
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn protect(address: usize, len: usize, protection: Native) -> Result<(), Error> {
+    let page_size = page_size();
+    let start = address & !(page_size - 1);
+    let end = (address + len.max(1)).next_multiple_of(page_size);
+
+    match unsafe { mprotect(start as *mut _, end - start, protection) } {
+        0 => Ok(()),
+        _ => Err(failed()),
+    }
+}
+
+/// Returns the error of the last call to the operating system as a protection failure.
+fn failed() -> Error {
+    Error::Protect {
+        code: io::Error::last_os_error()
+            .raw_os_error()
+            .unwrap_or_default(),
+    }
+}
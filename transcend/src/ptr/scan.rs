@@ -0,0 +1,709 @@
+//! Pattern scanning engine.
+//!
+//! Instead of comparing every window byte by byte, the scanner picks the two rarest bytes of a pattern
+//! as anchors, finds positions where both anchors match with SIMD compares and only then verifies the
+//! whole pattern.
+//!
+//! Scanning for many patterns at once instead feeds the longest literal run of each pattern into an
+//! Aho-Corasick automaton and walks the image a single time.
+
+use super::{Address, Pattern};
+use crate::Error;
+use aho_corasick::AhoCorasick;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::ops::Range;
+
+mod aho_corasick;
+
+/// How many window starts a single rayon task checks.
+const CHUNK: usize = 256 * 1024;
+
+/// Returns the first match of `pattern` in `slice`.
+///
+/// Like every scan, the returned address already includes the [capture offset](Pattern::offset).
+pub fn scan(slice: &[u8], pattern: &Pattern) -> Result<Address, Error> {
+    let searcher = Searcher::new(pattern);
+
+    chunks(slice, pattern)
+        .into_par_iter()
+        .find_map_first(|range| searcher.find(slice, range))
+        .map(|offset| address(slice, offset + pattern.offset()))
+        .ok_or(Error::PatternNotFound)
+}
+
+/// Returns the only match of `pattern` in `slice`, a signature that matches more than once is
+/// likely to point at the wrong code after an update.
+pub fn scan_unique(slice: &[u8], pattern: &Pattern) -> Result<Address, Error> {
+    unique(&scan_all(slice, pattern))
+}
+
+/// Returns every match of `pattern` in `slice`, sorted by address.
+#[must_use]
+pub fn scan_all(slice: &[u8], pattern: &Pattern) -> Vec<Address> {
+    let searcher = Searcher::new(pattern);
+
+    // Collecting a parallel iterator keeps the original order, so the offsets are already sorted.
+    let offsets: Vec<Vec<usize>> = chunks(slice, pattern)
+        .into_par_iter()
+        .map(|range| searcher.find_all(slice, range))
+        .collect();
+
+    offsets
+        .into_iter()
+        .flatten()
+        .map(|offset| address(slice, offset + pattern.offset()))
+        .collect()
+}
+
+/// Lazily yields every match of `pattern` in `slice` in ascending order, scanning sequentially.
+///
+/// Useful when only the first few matches are interesting, e.g. to check that a signature is unique.
+pub fn scan_iter<'a>(slice: &'a [u8], pattern: &'a Pattern) -> impl Iterator<Item = Address> + 'a {
+    let searcher = Searcher::new(pattern);
+    let mut start = 0;
+
+    std::iter::from_fn(move || {
+        let offset = searcher.find(slice, start..slice.len())?;
+        start = offset + 1;
+        Some(offset)
+    })
+    .map(|offset| address(slice, offset + pattern.offset()))
+}
+
+/// Scans for every pattern in a single pass and returns the only match of each, see
+/// [`scan_unique`].
+#[must_use]
+pub fn scan_many(slice: &[u8], patterns: &[Pattern]) -> Vec<Result<Address, Error>> {
+    scan_many_all(slice, patterns)
+        .iter()
+        .map(|matches| unique(matches))
+        .collect()
+}
+
+/// Scans for every pattern in a single pass and returns all matches of each, sorted by address.
+///
+/// An empty list means the pattern is missing from `slice`, more than one entry that it is ambiguous.
+#[must_use]
+pub fn scan_many_all(slice: &[u8], patterns: &[Pattern]) -> Vec<Vec<Address>> {
+    let anchors: Vec<Option<Range<usize>>> = patterns.iter().map(literal).collect();
+
+    // Only patterns with at least one concrete byte can be found through the automaton.
+    let (owners, needles): (Vec<usize>, Vec<&[u8]>) = anchors
+        .iter()
+        .enumerate()
+        .filter_map(|(index, anchor)| {
+            let anchor = anchor.clone()?;
+            Some((index, &patterns[index].bytes()[anchor]))
+        })
+        .unzip();
+
+    let automaton = AhoCorasick::new(&needles);
+    let longest = needles.iter().map(|needle| needle.len()).max().unwrap_or(1);
+
+    let found: Vec<Vec<(usize, usize)>> = (0..slice.len())
+        .step_by(CHUNK)
+        .map(|start| start..slice.len().min(start + CHUNK))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|ends| {
+            let mut found = Vec::new();
+
+            // Start early enough that needles crossing into this chunk are seen in full.
+            let from = ends.start.saturating_sub(longest - 1);
+
+            automaton.for_each(slice, from, ends, |needle, end| {
+                let index = owners[needle];
+                let Some(anchor) = &anchors[index] else {
+                    return;
+                };
+
+                if let Some(start) = (end - anchor.len()).checked_sub(anchor.start) {
+                    if patterns[index].matches(&slice[start..]) {
+                        found.push((index, start));
+                    }
+                }
+            });
+
+            found
+        })
+        .collect();
+
+    // Chunks are in order and every chunk reports matches in order, so each list stays sorted.
+    let mut matches = vec![Vec::new(); patterns.len()];
+    for (index, offset) in found.into_iter().flatten() {
+        matches[index].push(address(slice, offset + patterns[index].offset()));
+    }
+
+    for (index, pattern) in patterns.iter().enumerate() {
+        if anchors[index].is_none() {
+            matches[index] = scan_all(slice, pattern);
+        }
+    }
+
+    matches
+}
+
+/// Returns the longest run of concrete bytes in `pattern`, capped to keep the automaton small.
+fn literal(pattern: &Pattern) -> Option<Range<usize>> {
+    const LONGEST: usize = 16;
+
+    let mut longest: Option<Range<usize>> = None;
+    let mut start = 0;
+
+    for (index, &mask) in pattern.mask().iter().chain([&0x00]).enumerate() {
+        if mask != 0xFF {
+            if index > start
+                && longest
+                    .as_ref()
+                    .is_none_or(|longest| index - start > longest.len())
+            {
+                longest = Some(start..index.min(start + LONGEST));
+            }
+
+            start = index + 1;
+        }
+    }
+
+    longest
+}
+
+fn unique(matches: &[Address]) -> Result<Address, Error> {
+    match *matches {
+        [] => Err(Error::PatternNotFound),
+        [address] => Ok(address),
+        _ => Err(Error::AmbiguousPattern {
+            count: matches.len(),
+        }),
+    }
+}
+
+fn address(slice: &[u8], offset: usize) -> Address {
+    Address::new(slice.as_ptr() as usize + offset)
+}
+
+/// Splits the window starts of `slice` into ranges small enough to spread across threads.
+fn chunks(slice: &[u8], pattern: &Pattern) -> Vec<Range<usize>> {
+    let windows = (slice.len() + 1).saturating_sub(pattern.len());
+
+    (0..windows)
+        .step_by(CHUNK)
+        .map(|start| start..windows.min(start + CHUNK))
+        .collect()
+}
+
+/// Ranks of how common each byte value is in x86-64 machine code, from 0 (rarest) to 255 (most common).
+///
+/// Generated from the `.text` sections of a few large system libraries.
+#[rustfmt::skip]
+const BYTE_RANK: [u8; 256] = [
+    255, 245, 218, 215, 231, 213, 162, 186, 237, 163, 114, 97, 190, 160, 108, 252,
+    241, 203, 89, 109, 173, 142, 110, 101, 220, 54, 45, 35, 113, 49, 169, 234,
+    219, 76, 36, 38, 249, 155, 30, 29, 221, 179, 68, 95, 115, 52, 178, 120,
+    194, 222, 22, 46, 116, 123, 33, 34, 188, 227, 55, 149, 144, 138, 40, 75,
+    225, 246, 127, 187, 244, 228, 126, 168, 254, 239, 94, 83, 248, 208, 78, 71,
+    207, 50, 44, 176, 209, 197, 143, 154, 171, 122, 64, 182, 204, 201, 148, 121,
+    150, 17, 69, 164, 175, 42, 236, 13, 133, 25, 37, 41, 140, 43, 74, 139,
+    146, 23, 92, 117, 232, 216, 91, 119, 158, 28, 48, 90, 199, 118, 111, 152,
+    212, 166, 72, 243, 240, 238, 79, 135, 159, 251, 19, 250, 106, 242, 66, 56,
+    195, 12, 14, 57, 131, 86, 8, 26, 100, 6, 1, 11, 60, 32, 4, 15,
+    102, 9, 0, 20, 39, 18, 2, 3, 98, 10, 31, 24, 67, 16, 5, 53,
+    93, 21, 7, 27, 96, 51, 167, 82, 172, 105, 147, 62, 141, 88, 157, 128,
+    235, 224, 191, 223, 202, 198, 193, 229, 177, 174, 130, 61, 65, 77, 84, 73,
+    184, 145, 185, 104, 63, 70, 85, 112, 161, 81, 107, 134, 47, 59, 103, 196,
+    180, 132, 129, 58, 99, 80, 125, 156, 247, 230, 136, 211, 165, 153, 151, 206,
+    192, 124, 189, 233, 87, 137, 205, 183, 217, 170, 214, 200, 181, 210, 226, 253,
+];
+
+/// A single byte of the pattern that is checked before the full comparison.
+#[derive(Clone, Copy)]
+struct Anchor {
+    offset: usize,
+    value: u8,
+    mask: u8,
+}
+
+impl Anchor {
+    /// How likely a random byte of code matches this anchor, lower is better.
+    fn cost(&self) -> u32 {
+        match self.mask {
+            0xFF => BYTE_RANK[self.value as usize] as u32,
+            // Every wildcard bit doubles the amount of values that match.
+            mask => 0x100 * (1 << mask.count_zeros()),
+        }
+    }
+
+    #[inline(always)]
+    fn matches(&self, haystack: &[u8], start: usize) -> bool {
+        haystack[start + self.offset] & self.mask == self.value
+    }
+}
+
+/// A pattern prepared for scanning.
+pub(crate) struct Searcher<'p> {
+    pattern: &'p Pattern,
+    /// The two rarest bytes, `None` if the pattern consists of wildcards only.
+    anchors: Option<(Anchor, Anchor)>,
+}
+
+impl<'p> Searcher<'p> {
+    pub(crate) fn new(pattern: &'p Pattern) -> Self {
+        let mut anchors: Vec<Anchor> = pattern
+            .bytes()
+            .iter()
+            .zip(pattern.mask())
+            .enumerate()
+            .filter(|(_, (_, &mask))| mask != 0)
+            .map(|(offset, (&value, &mask))| Anchor {
+                offset,
+                value: value & mask,
+                mask,
+            })
+            .collect();
+
+        anchors.sort_by_key(Anchor::cost);
+
+        // A pattern with a single concrete byte uses it for both anchors.
+        let anchors = match anchors[..] {
+            [] => None,
+            [first] => Some((first, first)),
+            [first, second, ..] => Some((first, second)),
+        };
+
+        Self { pattern, anchors }
+    }
+
+    /// Returns the offset of the first match in `haystack` that starts within `starts`.
+    pub(crate) fn find(&self, haystack: &[u8], starts: Range<usize>) -> Option<usize> {
+        let mut found = None;
+        self.for_each(haystack, starts, |offset| {
+            found = Some(offset);
+            false
+        });
+        found
+    }
+
+    /// Returns the offsets of every match in `haystack` that starts within `starts`.
+    pub(crate) fn find_all(&self, haystack: &[u8], starts: Range<usize>) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.for_each(haystack, starts, |offset| {
+            found.push(offset);
+            true
+        });
+        found
+    }
+
+    /// Calls `f` with every match in ascending order until it returns `false`.
+    fn for_each(&self, haystack: &[u8], starts: Range<usize>, mut f: impl FnMut(usize) -> bool) {
+        if self.pattern.is_empty() || haystack.len() < self.pattern.len() {
+            return;
+        }
+
+        // Only windows that fit entirely into the haystack can match.
+        let end = starts.end.min(haystack.len() - self.pattern.len() + 1);
+        let starts = starts.start..end;
+
+        let Some((first, second)) = self.anchors else {
+            starts.take_while(|&start| f(start)).for_each(drop);
+            return;
+        };
+
+        let mut verify = |start: usize| !self.pattern.matches(&haystack[start..]) || f(start);
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                // SAFETY: AVX2 support was just checked.
+                return unsafe { x86::for_each_avx2(haystack, starts, first, second, &mut verify) };
+            }
+
+            // SAFETY: SSE2 is part of the x86-64 baseline.
+            unsafe { x86::for_each_sse2(haystack, starts, first, second, &mut verify) }
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            for_each_fallback(haystack, starts, first, second, &mut verify);
+        }
+    }
+}
+
+/// Checks the anchors one window at a time, used where no SIMD implementation is available and
+/// for the tail that does not fill a whole vector.
+#[inline(always)]
+fn for_each_fallback(
+    haystack: &[u8],
+    starts: Range<usize>,
+    first: Anchor,
+    second: Anchor,
+    verify: &mut impl FnMut(usize) -> bool,
+) -> bool {
+    starts
+        .filter(|&start| first.matches(haystack, start) && second.matches(haystack, start))
+        .all(verify)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{for_each_fallback, Anchor};
+    use std::arch::x86_64::*;
+    use std::ops::Range;
+
+    macro_rules! for_each_simd {
+        ($name:ident, $feature:literal, $vector:ty, $lanes:literal, $load:ident, $set1:ident, $and:ident, $cmpeq:ident, $movemask:ident) => {
+            /// Compares both anchors for a whole vector of window starts at once and verifies
+            /// the candidates where both match.
+            #[target_feature(enable = $feature)]
+            pub(super) unsafe fn $name(
+                haystack: &[u8],
+                starts: Range<usize>,
+                first: Anchor,
+                second: Anchor,
+                verify: &mut impl FnMut(usize) -> bool,
+            ) {
+                let ptr = haystack.as_ptr();
+                let (first_value, first_mask) = ($set1(first.value as i8), $set1(first.mask as i8));
+                let (second_value, second_mask) =
+                    ($set1(second.value as i8), $set1(second.mask as i8));
+
+                let mut start = starts.start;
+
+                // Every start below `starts.end` leaves room for a whole pattern, so loading `$lanes`
+                // bytes at any anchor offset of those starts stays inside the haystack.
+                while start + $lanes <= starts.end {
+                    let first_bytes =
+                        unsafe { $load(ptr.add(start + first.offset) as *const $vector) };
+                    let second_bytes =
+                        unsafe { $load(ptr.add(start + second.offset) as *const $vector) };
+
+                    let first_eq = $cmpeq($and(first_bytes, first_mask), first_value);
+                    let second_eq = $cmpeq($and(second_bytes, second_mask), second_value);
+
+                    let mut candidates = $movemask($and(first_eq, second_eq)) as u32;
+
+                    while candidates != 0 {
+                        if !verify(start + candidates.trailing_zeros() as usize) {
+                            return;
+                        }
+
+                        candidates &= candidates - 1;
+                    }
+
+                    start += $lanes;
+                }
+
+                for_each_fallback(haystack, start..starts.end, first, second, verify);
+            }
+        };
+    }
+
+    for_each_simd!(
+        for_each_sse2,
+        "sse2",
+        __m128i,
+        16,
+        _mm_loadu_si128,
+        _mm_set1_epi8,
+        _mm_and_si128,
+        _mm_cmpeq_epi8,
+        _mm_movemask_epi8
+    );
+
+    for_each_simd!(
+        for_each_avx2,
+        "avx2",
+        __m256i,
+        32,
+        _mm256_loadu_si256,
+        _mm256_set1_epi8,
+        _mm256_and_si256,
+        _mm256_cmpeq_epi8,
+        _mm256_movemask_epi8
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Xorshift, enough to spread patterns over haystacks without pulling in a dependency.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        /// Bytes from a small alphabet, so that patterns match often and anchors produce plenty of
+        /// false candidates.
+        fn bytes(&mut self, len: usize, alphabet: u8) -> Vec<u8> {
+            (0..len)
+                .map(|_| self.below(alphabet as usize) as u8)
+                .collect()
+        }
+
+        /// A window of `haystack` with some bytes or nibbles turned into wildcards.
+        fn pattern(&mut self, haystack: &[u8], len: usize) -> Pattern {
+            let start = self.below(haystack.len() - len + 1);
+            let mask: Vec<u8> = (0..len)
+                .map(|_| match self.below(6) {
+                    0 => 0x00,
+                    1 => 0xF0,
+                    2 => 0x0F,
+                    _ => 0xFF,
+                })
+                .collect();
+
+            Pattern::new(&haystack[start..start + len], mask).unwrap()
+        }
+    }
+
+    fn naive(slice: &[u8], pattern: &Pattern) -> Vec<usize> {
+        if pattern.len() > slice.len() {
+            return Vec::new();
+        }
+
+        slice
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| pattern.matches(window))
+            .map(|(offset, _)| offset + pattern.offset())
+            .collect()
+    }
+
+    fn offsets(slice: &[u8], addresses: impl IntoIterator<Item = Address>) -> Vec<usize> {
+        addresses
+            .into_iter()
+            .map(|address| address.as_usize() - slice.as_ptr() as usize)
+            .collect()
+    }
+
+    type Verify<'a> = dyn FnMut(usize) -> bool + 'a;
+
+    /// Returns the matches of every anchor implementation, not just the one this CPU picks.
+    fn implementations(haystack: &[u8], pattern: &Pattern) -> Vec<Vec<usize>> {
+        let Some((first, second)) = Searcher::new(pattern).anchors else {
+            return Vec::new();
+        };
+        let starts = 0..(haystack.len() + 1).saturating_sub(pattern.len());
+
+        let run = |f: &dyn Fn(&mut Verify)| {
+            let mut found = Vec::new();
+            f(&mut |start| {
+                if pattern.matches(&haystack[start..]) {
+                    found.push(start + pattern.offset());
+                }
+                true
+            });
+            found
+        };
+
+        let mut results = Vec::new();
+        results.push(run(&|mut verify| {
+            for_each_fallback(haystack, starts.clone(), first, second, &mut verify);
+        }));
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            results.push(run(&|mut verify| unsafe {
+                x86::for_each_sse2(haystack, starts.clone(), first, second, &mut verify)
+            }));
+
+            if is_x86_feature_detected!("avx2") {
+                results.push(run(&|mut verify| unsafe {
+                    x86::for_each_avx2(haystack, starts.clone(), first, second, &mut verify)
+                }));
+            }
+        }
+
+        results
+    }
+
+    fn check(slice: &[u8], pattern: &Pattern) {
+        let expected = naive(slice, pattern);
+
+        for found in implementations(slice, pattern) {
+            assert_eq!(found, expected);
+        }
+
+        assert_eq!(offsets(slice, scan_all(slice, pattern)), expected);
+        assert_eq!(offsets(slice, scan_iter(slice, pattern)), expected);
+        assert_eq!(
+            scan(slice, pattern)
+                .ok()
+                .map(|address| offsets(slice, [address])[0]),
+            expected.first().copied()
+        );
+    }
+
+    #[test]
+    fn random() {
+        let mut random = Random(0x2545_F491_4F6C_DD1D);
+
+        for _ in 0..500 {
+            let len = 1 + random.below(2000);
+            let haystack = random.bytes(len, 4);
+            let len = 1 + random.below(haystack.len().min(12));
+            let pattern = random.pattern(&haystack, len);
+
+            check(&haystack, &pattern);
+        }
+    }
+
+    #[test]
+    fn single_byte() {
+        let mut random = Random(0x9E37_79B9_7F4A_7C15);
+        let haystack = random.bytes(1000, 16);
+
+        for value in 0..16 {
+            check(&haystack, &Pattern::from([value].as_slice()));
+        }
+    }
+
+    #[test]
+    fn single_anchor() {
+        let mut random = Random(0xD1B5_4A32_D192_ED03);
+        let haystack = random.bytes(1000, 8);
+
+        for value in 0..8 {
+            let pattern = format!("?? ?? {value:02X} ??").parse().unwrap();
+            check(&haystack, &pattern);
+        }
+    }
+
+    #[test]
+    fn nibble_masks() {
+        let mut random = Random(0x94D0_49BB_1331_11EB);
+        let haystack = random.bytes(4000, 255);
+
+        for pattern in ["4? 8B", "?5 ?? ?C", "E? ?? ?? ?? ?? 4?", "?0"] {
+            check(&haystack, &pattern.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn vector_tail() {
+        let pattern: Pattern = "E8 ?? 3? ?? C3".parse().unwrap();
+
+        // Matches in the last bytes that do not fill a whole vector, up to the very last window.
+        for len in pattern.len()..100 {
+            for start in len.saturating_sub(40)..=len - pattern.len() {
+                let mut haystack = vec![0xE8; len];
+                haystack[start..start + 5].copy_from_slice(&[0xE8, 0x00, 0x31, 0x00, 0xC3]);
+
+                check(&haystack, &pattern);
+            }
+        }
+    }
+
+    /// Compares the single pass over all patterns with scanning for each of them on its own.
+    fn check_many(slice: &[u8], patterns: &[Pattern]) {
+        let all = scan_many_all(slice, patterns);
+        let many = scan_many(slice, patterns);
+
+        for (index, pattern) in patterns.iter().enumerate() {
+            assert_eq!(all[index], scan_all(slice, pattern), "{pattern}");
+            assert_eq!(many[index], scan_unique(slice, pattern), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn many_random() {
+        let mut random = Random(0xBF58_476D_1CE4_E5B9);
+
+        for _ in 0..50 {
+            let len = 1 + random.below(3000);
+            let haystack = random.bytes(len, 6);
+            let patterns: Vec<Pattern> = (0..1 + random.below(20))
+                .map(|_| {
+                    let len = 1 + random.below(haystack.len().min(24));
+                    random.pattern(&haystack, len)
+                })
+                .collect();
+
+            check_many(&haystack, &patterns);
+        }
+    }
+
+    #[test]
+    fn many_overlapping() {
+        let haystack = b"abcabcabd abcd bcd cd d abcabcabd";
+        let patterns: Vec<Pattern> = ["abcabd", "abc", "bc", "c", "abcd", "cabcab", "d"]
+            .into_iter()
+            .map(|needle| Pattern::from(needle.as_bytes()))
+            .collect();
+
+        check_many(haystack, &patterns);
+    }
+
+    #[test]
+    fn many_wildcard_first() {
+        let haystack = [
+            0x48, 0x8B, 0x05, 0x11, 0x22, 0x33, 0x44, 0xC3, 0xE8, 0x00, 0x00,
+        ];
+        let patterns: Vec<Pattern> = ["?? 8B 05", "?? ?? 05 &?? ?? ?? ?? C3", "?? ??", "?8 ?? 0?"]
+            .into_iter()
+            .map(|pattern| pattern.parse().unwrap())
+            .collect();
+
+        check_many(&haystack, &patterns);
+    }
+
+    #[test]
+    fn many_chunk_boundary() {
+        let mut haystack = vec![0x00; CHUNK * 2 + 64];
+        let needle = [0x48, 0x8B, 0x05, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0xC3];
+
+        // Needles that end right before, start right at and straddle the boundary of a chunk.
+        for start in [CHUNK - 10, CHUNK, CHUNK + 20, CHUNK * 2 - 5, CHUNK * 2 + 54] {
+            haystack[start..start + needle.len()].copy_from_slice(&needle);
+        }
+
+        let patterns: Vec<Pattern> = [
+            "48 8B 05 11 22 33 44 55 66 C3",
+            "48 8B 05 ?? ?? ?? ?? 55 66 C3",
+            "?? 11 22 33",
+            "66 C3 00",
+        ]
+        .into_iter()
+        .map(|pattern| pattern.parse().unwrap())
+        .collect();
+
+        check_many(&haystack, &patterns);
+        assert_eq!(scan_many_all(&haystack, &patterns)[0].len(), 5);
+        assert_eq!(
+            scan_many(&haystack, &patterns)[0],
+            Err(Error::AmbiguousPattern { count: 5 })
+        );
+    }
+
+    #[test]
+    fn wildcards_only() {
+        let haystack = [0x90; 40];
+        check(&haystack, &"?? ?? ??".parse().unwrap());
+        check(&haystack[..2], &"?? ?? ??".parse().unwrap());
+    }
+
+    #[test]
+    fn longer_than_haystack() {
+        check(&[0x48, 0x8B], &"48 8B 05".parse().unwrap());
+        check(&[], &"48".parse().unwrap());
+    }
+
+    #[test]
+    fn chunk_boundary() {
+        let mut haystack = vec![0x00; CHUNK * 2 + 100];
+        for offset in [0, CHUNK - 4, CHUNK - 1, CHUNK + 2, CHUNK * 2 + 97] {
+            haystack[offset..offset + 3].copy_from_slice(&[0xE8, 0x12, 0x34]);
+        }
+
+        check(&haystack, &"E8 12 34".parse().unwrap());
+        check(&haystack, &"E8 ?? 34".parse().unwrap());
+    }
+}
@@ -0,0 +1,94 @@
+//! A small Aho-Corasick automaton for finding many literal needles in a single pass.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+const NONE: u32 = u32::MAX;
+
+/// A deterministic Aho-Corasick automaton with dense transitions.
+pub(super) struct AhoCorasick {
+    transitions: Vec<[u32; 256]>,
+    /// The needles that end in each state, including those reached through failure links.
+    outputs: Vec<Vec<usize>>,
+}
+
+impl AhoCorasick {
+    pub(super) fn new(needles: &[&[u8]]) -> Self {
+        let mut transitions = vec![[NONE; 256]];
+        let mut outputs = vec![Vec::new()];
+
+        // Build the trie of all needles.
+        for (index, needle) in needles.iter().enumerate() {
+            let mut state = 0;
+
+            for &byte in *needle {
+                if transitions[state][byte as usize] == NONE {
+                    transitions[state][byte as usize] = transitions.len() as u32;
+                    transitions.push([NONE; 256]);
+                    outputs.push(Vec::new());
+                }
+
+                state = transitions[state][byte as usize] as usize;
+            }
+
+            outputs[state].push(index);
+        }
+
+        // Resolve the failure links breadth first, so the link of every state is complete before the
+        // states below it need it, and fold them into the transitions.
+        let mut failure = vec![0; transitions.len()];
+        let mut queue = VecDeque::new();
+
+        for next in transitions[0].iter_mut() {
+            match *next {
+                NONE => *next = 0,
+                state => queue.push_back(state as usize),
+            }
+        }
+
+        while let Some(state) = queue.pop_front() {
+            let inherited = outputs[failure[state]].clone();
+            outputs[state].extend(inherited);
+
+            let fallback = transitions[failure[state]];
+
+            for (byte, next) in transitions[state].iter_mut().enumerate() {
+                match *next {
+                    NONE => *next = fallback[byte],
+                    next => {
+                        failure[next as usize] = fallback[byte] as usize;
+                        queue.push_back(next as usize);
+                    }
+                }
+            }
+        }
+
+        Self {
+            transitions,
+            outputs,
+        }
+    }
+
+    /// Calls `f` with the needle index and the end offset of every occurrence in `haystack` whose last
+    /// byte lies within `ends`, walking the automaton from `from` onwards.
+    pub(super) fn for_each(
+        &self,
+        haystack: &[u8],
+        from: usize,
+        ends: Range<usize>,
+        mut f: impl FnMut(usize, usize),
+    ) {
+        let mut state = 0;
+
+        for (position, &byte) in haystack[from..ends.end].iter().enumerate() {
+            let position = from + position;
+            state = self.transitions[state][byte as usize] as usize;
+
+            if position >= ends.start {
+                for &needle in &self.outputs[state] {
+                    f(needle, position + 1);
+                }
+            }
+        }
+    }
+}
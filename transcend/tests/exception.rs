@@ -0,0 +1,82 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use std::arch::global_asm;
+use std::sync::atomic::{AtomicU64, Ordering};
+use transcend::ptr::{breakpoint_hook, page_hook};
+
+// The guarded function has a page to itself, so no code the signal handler runs shares it.
+global_asm!(
+    ".globl transcend_test_trap",
+    ".p2align 4",
+    "transcend_test_trap:",
+    "    lea eax, [rdi + rsi]",
+    "    ret",
+    "",
+    ".p2align 12",
+    ".globl transcend_test_guarded",
+    "transcend_test_guarded:",
+    "    mov eax, edi",
+    ".globl transcend_test_guarded_add",
+    "transcend_test_guarded_add:",
+    "    add eax, esi",
+    "    add eax, 1",
+    "    ret",
+    ".p2align 12",
+);
+
+extern "C" {
+    fn transcend_test_trap(a: u32, b: u32) -> u32;
+    fn transcend_test_guarded(a: u32, b: u32) -> u32;
+    static transcend_test_guarded_add: u8;
+}
+
+#[test]
+fn breakpoint() {
+    static SEEN: AtomicU64 = AtomicU64::new(0);
+
+    let address = transcend_test_trap as *const u8;
+    let original = unsafe { *address };
+
+    let hook = unsafe {
+        breakpoint_hook(address.into(), |context| {
+            SEEN.store(context.rdi, Ordering::Relaxed);
+            context.rsi = 100;
+        })
+    }
+    .unwrap();
+
+    assert_eq!(unsafe { *address }, 0xCC);
+    assert_eq!(unsafe { transcend_test_trap(2, 3) }, 102);
+    assert_eq!(unsafe { transcend_test_trap(5, 3) }, 105);
+    assert_eq!(SEEN.load(Ordering::Relaxed), 5);
+
+    drop(hook);
+    assert_eq!(unsafe { *address }, original);
+    assert_eq!(unsafe { transcend_test_trap(2, 3) }, 5);
+}
+
+#[test]
+fn page() {
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+
+    let address = &raw const transcend_test_guarded_add;
+    let original = unsafe { *address };
+
+    let hook = unsafe {
+        page_hook(address.into(), |context| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            context.rsi = 100;
+        })
+    }
+    .unwrap();
+
+    // The instructions around the hooked one are stepped over.
+    assert_eq!(unsafe { transcend_test_guarded(2, 3) }, 103);
+    assert_eq!(unsafe { transcend_test_guarded(5, 3) }, 106);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+
+    drop(hook);
+    assert_eq!(unsafe { *address }, original);
+    assert_eq!(unsafe { transcend_test_guarded(2, 3) }, 6);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+}
@@ -0,0 +1,64 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use libc::{c_char, c_int, c_void, dlopen, dlsym, RTLD_NOW};
+use std::mem::transmute;
+use std::process;
+use transcend::ptr::{got_hook, module, modules, HookError};
+use transcend::Error;
+
+type GetPid = unsafe extern "C" fn() -> i32;
+type SameName = unsafe extern "C" fn(*const c_char, *const c_char) -> c_int;
+
+unsafe extern "C" fn getpid() -> i32 {
+    42
+}
+
+unsafe extern "C" fn same_name(_: *const c_char, _: *const c_char) -> c_int {
+    42
+}
+
+#[test]
+fn hook_getpid() {
+    let program = &modules().unwrap()[0];
+    let pid = process::id();
+
+    // The standard library calls `getpid` from libc through the GOT of the test binary, which full
+    // RELRO made read-only.
+    let hook = unsafe { got_hook(program, "getpid", getpid as GetPid) }.unwrap();
+    assert_eq!(process::id(), 42);
+    assert_eq!(unsafe { (hook.original())() } as u32, pid);
+
+    drop(hook);
+    assert_eq!(process::id(), pid);
+}
+
+#[test]
+fn hook_dlopen() {
+    let (a, b) = (c"example.com".as_ptr(), c"EXAMPLE.com.".as_ptr());
+
+    let handle = unsafe { dlopen(c"libresolv.so.2".as_ptr(), RTLD_NOW) };
+    assert!(!handle.is_null());
+    let resolv = module("libresolv.so.2").unwrap();
+
+    // Since glibc 2.34 `ns_samename` in libresolv only calls the implementation in libc, through
+    // the GOT of libresolv.
+    let samename = unsafe { dlsym(handle, c"ns_samename".as_ptr()) };
+    assert!(!samename.is_null());
+    let samename = unsafe { transmute::<*mut c_void, SameName>(samename) };
+    assert_eq!(unsafe { samename(a, b) }, 1);
+
+    let hook = unsafe { got_hook(&resolv, "__libc_ns_samename", same_name as SameName) }.unwrap();
+    assert_eq!(unsafe { samename(a, b) }, 42);
+    assert_eq!(unsafe { (hook.original())(a, b) }, 1);
+
+    drop(hook);
+    assert_eq!(unsafe { samename(a, b) }, 1);
+}
+
+#[test]
+fn not_imported() {
+    let program = &modules().unwrap()[0];
+    let error = unsafe { got_hook(program, "transcend_missing", getpid as GetPid) }.unwrap_err();
+
+    assert_eq!(error, Error::Hook(HookError::NotImported));
+}
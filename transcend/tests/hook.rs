@@ -0,0 +1,200 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use libc::{
+    mmap, mprotect, MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_EXEC, PROT_NONE, PROT_READ,
+    PROT_WRITE,
+};
+use std::arch::global_asm;
+use std::mem::transmute;
+use std::ptr::null_mut;
+use std::sync::Mutex;
+use transcend::ptr::{Detour, HookError};
+use transcend::Error;
+
+// Functions with a known prologue, the compiler is free to shrink anything written in Rust below
+// the size of a jump.
+global_asm!(
+    ".globl transcend_test_add",
+    ".p2align 4",
+    "transcend_test_add:",
+    "    push rbp",
+    "    mov rbp, rsp",
+    "    lea eax, [rdi + rsi]",
+    "    pop rbp",
+    "    ret",
+    "",
+    ".globl transcend_test_relative",
+    ".p2align 4",
+    "transcend_test_relative:",
+    "    test edi, edi",
+    "    jz 2f",
+    "    lea rax, [rip + transcend_test_value]",
+    "    mov eax, [rax]",
+    "    ret",
+    "2:  mov eax, -1",
+    "    ret",
+    "",
+    ".globl transcend_test_short",
+    ".p2align 4",
+    "transcend_test_short:",
+    "    xor eax, eax",
+    "    ret",
+    "",
+    ".globl transcend_test_loop",
+    ".p2align 4",
+    "transcend_test_loop:",
+    "    push rbx",
+    "    mov ebx, edi",
+    "2:  dec ebx",
+    "    jnz 2b",
+    "    mov eax, ebx",
+    "    pop rbx",
+    "    ret",
+    "",
+    ".data",
+    ".p2align 2",
+    "transcend_test_value:",
+    "    .long 42",
+    ".text",
+);
+
+extern "C" {
+    fn transcend_test_add(a: u32, b: u32) -> u32;
+    fn transcend_test_relative(a: u32) -> u32;
+    fn transcend_test_short() -> u32;
+    fn transcend_test_loop(a: u32) -> u32;
+}
+
+type Add = unsafe extern "C" fn(u32, u32) -> u32;
+type Relative = unsafe extern "C" fn(u32) -> u32;
+type Short = unsafe extern "C" fn() -> u32;
+
+static ADD: Mutex<Option<Detour<Add>>> = Mutex::new(None);
+static RELATIVE: Mutex<Option<Detour<Relative>>> = Mutex::new(None);
+
+unsafe extern "C" fn add(a: u32, b: u32) -> u32 {
+    let original = ADD.lock().unwrap().as_ref().unwrap().original();
+    unsafe { original(a, b) * 10 }
+}
+
+unsafe extern "C" fn relative(a: u32) -> u32 {
+    let original = RELATIVE.lock().unwrap().as_ref().unwrap().original();
+    unsafe { original(a).wrapping_add(1) }
+}
+
+unsafe extern "C" fn short() -> u32 {
+    1
+}
+
+unsafe extern "C" fn sum(a: u32, b: u32) -> u32 {
+    a + b + 100
+}
+
+#[test]
+fn detour() {
+    let target: Add = transcend_test_add;
+    assert_eq!(unsafe { target(1, 2) }, 3);
+
+    let detour = unsafe { Detour::new(target, add as Add) }.unwrap();
+    assert!(detour.is_enabled());
+    assert_eq!(unsafe { detour.call_original(1, 2) }, 3);
+    *ADD.lock().unwrap() = Some(detour);
+
+    assert_eq!(unsafe { target(1, 2) }, 30);
+
+    let guard = ADD.lock().unwrap();
+    let detour = guard.as_ref().unwrap();
+    unsafe { detour.disable() }.unwrap();
+    assert!(!detour.is_enabled());
+    assert_eq!(unsafe { target(1, 2) }, 3);
+
+    unsafe { detour.enable() }.unwrap();
+    drop(guard);
+    assert_eq!(unsafe { target(1, 2) }, 30);
+
+    // Dropping the detour restores the original code.
+    ADD.lock().unwrap().take();
+    assert_eq!(unsafe { target(1, 2) }, 3);
+}
+
+#[test]
+fn relocation() {
+    let target: Relative = transcend_test_relative;
+    assert_eq!(unsafe { target(1) }, 42);
+    assert_eq!(unsafe { target(0) }, u32::MAX);
+
+    // The moved `jz` and `lea [rip + ..]` still reach their targets from the trampoline.
+    let detour = unsafe { Detour::new(target, relative as Relative) }.unwrap();
+    *RELATIVE.lock().unwrap() = Some(detour);
+
+    assert_eq!(unsafe { target(1) }, 43);
+    assert_eq!(unsafe { target(0) }, 0);
+
+    RELATIVE.lock().unwrap().take();
+    assert_eq!(unsafe { target(1) }, 42);
+}
+
+#[test]
+fn too_short() {
+    let target: Short = transcend_test_short;
+    let error = unsafe { Detour::new(target, short as Short) }.unwrap_err();
+
+    assert_eq!(error, Error::Hook(HookError::TooShort { len: 3 }));
+    assert_eq!(unsafe { target() }, 0);
+}
+
+#[test]
+fn branch_into_prologue() {
+    let target: Relative = transcend_test_loop;
+    let error = unsafe { Detour::new(target, relative as Relative) }.unwrap_err();
+
+    assert_eq!(
+        error,
+        Error::Hook(HookError::BranchIntoPrologue { from: 5, to: 3 })
+    );
+    assert_eq!(unsafe { target(3) }, 0);
+}
+
+#[test]
+fn end_of_mapping() {
+    const PAGE: usize = 4096;
+
+    // A function that returns through an earlier `ret` and ends in a call that does not return,
+    // right before a page that can not be read and that decoding must not touch.
+    let code: &[u8] = &[
+        0x0F, 0x0B, // ud2
+        0xC3, // ret
+        0x8D, 0x04, 0x37, // lea eax, [rdi + rsi]    <- the target
+        0x85, 0xC0, // test eax, eax
+        0x75, 0xF8, // jnz to the ret
+        0xE8, 0xF1, 0xFF, 0xFF, 0xFF, // call to the ud2
+    ];
+
+    let page = unsafe {
+        mmap(
+            null_mut(),
+            2 * PAGE,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    assert_ne!(page, MAP_FAILED);
+
+    let page = page.cast::<u8>();
+    let start = unsafe { page.add(PAGE - code.len()) };
+    unsafe {
+        start.copy_from(code.as_ptr(), code.len());
+        assert_eq!(mprotect(page.cast(), PAGE, PROT_READ | PROT_EXEC), 0);
+        assert_eq!(mprotect(page.add(PAGE).cast(), PAGE, PROT_NONE), 0);
+    }
+
+    let target = unsafe { transmute::<*mut u8, Add>(start.add(3)) };
+    let detour = unsafe { Detour::new(target, sum as Add) }.unwrap();
+    assert_eq!(unsafe { target(1, 2) }, 103);
+    assert_eq!(unsafe { detour.call_original(1, 2) }, 3);
+
+    drop(detour);
+    assert_eq!(unsafe { target(1, 2) }, 3);
+}
@@ -0,0 +1,130 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use std::arch::global_asm;
+use std::sync::Mutex;
+use transcend::ptr::{mid_hook, Address, Context};
+
+const CARRY: u64 = 1 << 0;
+const DIRECTION: u64 = 1 << 10;
+
+// Loads known values into every register, hits the hooked `nop` and writes `rax`, `xmm0`,
+// `rflags`, `rcx` and `r15` to `out` right after it.
+global_asm!(
+    ".globl transcend_test_mid",
+    ".p2align 4",
+    "transcend_test_mid:",
+    "    push rbx",
+    "    push rbp",
+    "    push r12",
+    "    push r13",
+    "    push r14",
+    "    push r15",
+    "    push rdi",
+    "    mov eax, 0xA0",
+    "    movq xmm0, rax",
+    "    mov eax, 0xA7",
+    "    movq xmm7, rax",
+    "    mov eax, 0xAF",
+    "    movq xmm15, rax",
+    "    mov eax, 0x10",
+    "    mov ecx, 0x11",
+    "    mov edx, 0x12",
+    "    mov ebx, 0x13",
+    "    mov ebp, 0x15",
+    "    mov esi, 0x16",
+    "    mov edi, 0x17",
+    "    mov r8d, 0x18",
+    "    mov r9d, 0x19",
+    "    mov r10d, 0x1A",
+    "    mov r11d, 0x1B",
+    "    mov r12d, 0x1C",
+    "    mov r13d, 0x1D",
+    "    mov r14d, 0x1E",
+    "    mov r15d, 0x1F",
+    "    stc",
+    "    std",
+    ".globl transcend_test_mid_point",
+    "transcend_test_mid_point:",
+    "    nop dword ptr [rax + rax]",
+    "    pushfq",
+    "    cld",
+    "    mov rdi, [rsp + 8]",
+    "    pop qword ptr [rdi + 24]",
+    "    mov [rdi], rax",
+    "    movups [rdi + 8], xmm0",
+    "    mov [rdi + 32], rcx",
+    "    mov [rdi + 40], r15",
+    "    pop rdi",
+    "    pop r15",
+    "    pop r14",
+    "    pop r13",
+    "    pop r12",
+    "    pop rbp",
+    "    pop rbx",
+    "    ret",
+);
+
+extern "C" {
+    fn transcend_test_mid(out: *mut [u64; 6]);
+    fn transcend_test_mid_point();
+}
+
+/// Returns `rax`, `xmm0`, `rflags`, `rcx` and `r15` after the hooked instruction.
+fn run(out: &mut [u64; 6]) -> (u64, u128, u64, u64, u64) {
+    unsafe { transcend_test_mid(out) };
+
+    let xmm0 = out[1] as u128 | (out[2] as u128) << 64;
+    (out[0], xmm0, out[3], out[4], out[5])
+}
+
+#[test]
+fn registers() {
+    static SEEN: Mutex<Option<(Context, usize)>> = Mutex::new(None);
+    let mut out = [0; 6];
+
+    let (rax, xmm0, rflags, rcx, r15) = run(&mut out);
+    assert_eq!((rax, xmm0, rcx, r15), (0x10, 0xA0, 0x11, 0x1F));
+    assert_eq!(rflags & (CARRY | DIRECTION), CARRY | DIRECTION);
+
+    let point = Address::from_ptr(transcend_test_mid_point as *const ());
+    let callback = |context: &mut Context| {
+        let top = unsafe { *(context.rsp as *const usize) };
+        *SEEN.lock().unwrap() = Some((*context, top));
+
+        context.rax = 0x20;
+        context.xmm[0] = 0xB1 << 64 | 0xB0;
+        context.rflags &= !CARRY;
+    };
+    let hook = unsafe { mid_hook(point, callback) }.unwrap();
+
+    let (rax, xmm0, rflags, rcx, r15) = run(&mut out);
+    let (seen, top) = SEEN.lock().unwrap().take().unwrap();
+
+    // The callback sees the registers as they were at the hooked instruction.
+    let registers = [
+        seen.rax, seen.rcx, seen.rdx, seen.rbx, seen.rbp, seen.rsi, seen.rdi, seen.r8, seen.r9,
+        seen.r10, seen.r11, seen.r12, seen.r13, seen.r14, seen.r15,
+    ];
+    assert_eq!(
+        registers,
+        [
+            0x10, 0x11, 0x12, 0x13, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E,
+            0x1F
+        ]
+    );
+    assert_eq!((seen.xmm[0], seen.xmm[7], seen.xmm[15]), (0xA0, 0xA7, 0xAF));
+    assert_eq!(seen.rflags & (CARRY | DIRECTION), CARRY | DIRECTION);
+
+    // `rsp` pointed to the last value the fixture pushed, the pointer to its output.
+    assert_eq!(top, out.as_ptr() as usize);
+
+    // The changes of the callback are in place after the hooked instruction, everything else is untouched.
+    assert_eq!((rax, xmm0, rcx, r15), (0x20, 0xB1 << 64 | 0xB0, 0x11, 0x1F));
+    assert_eq!(rflags & (CARRY | DIRECTION), DIRECTION);
+
+    drop(hook);
+    let (rax, xmm0, rflags, _, _) = run(&mut out);
+    assert_eq!((rax, xmm0), (0x10, 0xA0));
+    assert_eq!(rflags & CARRY, CARRY);
+    assert!(SEEN.lock().unwrap().is_none());
+}
@@ -0,0 +1,167 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use libc::{
+    mmap, mprotect, MAP_ANONYMOUS, MAP_FAILED, MAP_PRIVATE, PROT_EXEC, PROT_READ, PROT_WRITE,
+};
+use std::fs;
+use std::ptr::null_mut;
+use transcend::ptr::{Address, Patch, ProtectGuard, Protection};
+
+type Function = unsafe extern "C" fn() -> u32;
+
+const PAGE: usize = 4096;
+
+/// Maps two pages of code, which the tests own so they can patch them freely.
+///
+/// The first page starts with `mov eax, 1; add eax, 1; ret` and the second one with
+/// `mov eax, 7; ret`.
+fn code() -> Address {
+    let page = unsafe {
+        mmap(
+            null_mut(),
+            2 * PAGE,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    assert_ne!(page, MAP_FAILED);
+
+    let first: &[u8] = &[0xB8, 0x01, 0x00, 0x00, 0x00, 0x83, 0xC0, 0x01, 0xC3];
+    let second: &[u8] = &[0xB8, 0x07, 0x00, 0x00, 0x00, 0xC3];
+
+    let page = page.cast::<u8>();
+    unsafe {
+        page.copy_from(first.as_ptr(), first.len());
+        page.add(PAGE).copy_from(second.as_ptr(), second.len());
+        assert_eq!(mprotect(page.cast(), 2 * PAGE, PROT_READ | PROT_EXEC), 0);
+    }
+
+    Address::from_ptr(page)
+}
+
+/// Returns the permissions `/proc/self/maps` lists for the page that holds `address`, e.g. `r-xp`.
+fn permissions(address: Address) -> String {
+    let maps = fs::read_to_string("/proc/self/maps").unwrap();
+
+    maps.lines()
+        .find_map(|line| {
+            let (range, rest) = line.split_once(' ')?;
+            let (start, end) = range.split_once('-')?;
+            let range =
+                usize::from_str_radix(start, 16).ok()?..usize::from_str_radix(end, 16).ok()?;
+            range
+                .contains(&address.as_usize())
+                .then(|| rest[..4].to_owned())
+        })
+        .unwrap()
+}
+
+fn call(address: Address) -> u32 {
+    unsafe { address.as_fn::<Function>()() }
+}
+
+#[test]
+fn patch() {
+    let code = code();
+    assert_eq!(call(code), 2);
+
+    // The immediate of `mov eax, 1`.
+    let patch = unsafe { Patch::new(code + 1, &[0x05]) }.unwrap();
+    assert!(patch.is_enabled());
+    assert_eq!(patch.address(), code + 1);
+    assert_eq!(patch.original(), &[0x01]);
+    assert_eq!(patch.bytes(), &[0x05]);
+    assert_eq!(call(code), 6);
+    assert_eq!(permissions(code), "r-xp");
+
+    unsafe { patch.disable() }.unwrap();
+    assert!(!patch.is_enabled());
+    assert_eq!(call(code), 2);
+
+    unsafe { patch.enable() }.unwrap();
+    assert_eq!(call(code), 6);
+
+    assert!(!unsafe { patch.toggle() }.unwrap());
+    assert_eq!(call(code), 2);
+    assert!(unsafe { patch.toggle() }.unwrap());
+    assert_eq!(call(code), 6);
+
+    unsafe { patch.revert() }.unwrap();
+    assert_eq!(call(code), 2);
+    assert_eq!(permissions(code), "r-xp");
+}
+
+#[test]
+fn patch_disabled_and_dropped() {
+    let code = code();
+
+    let patch = unsafe { Patch::new_disabled(code + 1, &[0x05]) };
+    assert!(!patch.is_enabled());
+    assert_eq!(call(code), 2);
+
+    unsafe { patch.enable() }.unwrap();
+    assert_eq!(call(code), 6);
+
+    drop(patch);
+    assert_eq!(call(code), 2);
+}
+
+#[test]
+fn nop_ret_and_jmp() {
+    let code = code();
+
+    // `add eax, 1` is three bytes, filled with a single `nop`.
+    let nop = unsafe { Patch::nop(code + 5, 3) }.unwrap();
+    assert_eq!(nop.bytes(), &[0x0F, 0x1F, 0x00]);
+    assert_eq!(call(code), 1);
+    drop(nop);
+
+    // Returns right after `mov eax, 1`.
+    let ret = unsafe { Patch::ret(code + 5) }.unwrap();
+    assert_eq!(ret.bytes(), &[0xC3]);
+    assert_eq!(call(code), 1);
+    drop(ret);
+
+    let jmp = unsafe { Patch::jmp(code, code + PAGE) }.unwrap();
+    assert_eq!(jmp.bytes()[0], 0xE9);
+    assert_eq!(call(code), 7);
+    drop(jmp);
+
+    assert_eq!(call(code), 2);
+}
+
+#[test]
+fn protect_guard() {
+    let code = code();
+    assert_eq!(permissions(code), "r-xp");
+
+    let guard = unsafe { ProtectGuard::new(code, 1, Protection::ReadWrite) }.unwrap();
+    assert_eq!(permissions(code), "rw-p");
+    assert_eq!(permissions(code + PAGE), "r-xp");
+
+    drop(guard);
+    assert_eq!(permissions(code), "r-xp");
+    assert_eq!(call(code), 2);
+}
+
+#[test]
+fn protect_guard_across_pages() {
+    let code = code();
+
+    // Give the two pages different protections, each has to get its own one back.
+    let first = unsafe { ProtectGuard::new(code, 1, Protection::Read) }.unwrap();
+    assert_eq!(permissions(code), "r--p");
+
+    let both = unsafe { ProtectGuard::new(code + PAGE - 1, 2, Protection::ReadWrite) }.unwrap();
+    assert_eq!(permissions(code), "rw-p");
+    assert_eq!(permissions(code + PAGE), "rw-p");
+
+    both.restore().unwrap();
+    assert_eq!(permissions(code), "r--p");
+    assert_eq!(permissions(code + PAGE), "r-xp");
+
+    first.restore().unwrap();
+    assert_eq!(permissions(code), "r-xp");
+}
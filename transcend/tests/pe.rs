@@ -0,0 +1,150 @@
+use transcend::ptr::{imports, Import, Rva};
+use transcend::Error;
+
+/// Copies `bytes` to `offset` in `file`.
+fn put(file: &mut [u8], offset: usize, bytes: &[u8]) {
+    file[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn u16(value: u16) -> [u8; 2] {
+    value.to_le_bytes()
+}
+
+fn u32(value: u32) -> [u8; 4] {
+    value.to_le_bytes()
+}
+
+fn u64(value: u64) -> [u8; 8] {
+    value.to_le_bytes()
+}
+
+/// A PE32+ DLL whose `.rdata` section lives at RVA 0x2000 but file offset 0x400. It imports
+/// `CreateFileW` and `LoadLibraryW` from `KERNEL32.dll` by name and ordinal 17 from `USER32.dll`.
+fn fixture() -> Vec<u8> {
+    const RDATA: usize = 0x400;
+    // Where RVA 0x2000 and up is stored in the file.
+    let rdata = |rva: usize| RDATA + rva - 0x2000;
+
+    let mut file = vec![0; 0x600];
+
+    // DOS header, pointing to the PE headers.
+    put(&mut file, 0, b"MZ");
+    put(&mut file, 0x3C, &u32(0x40));
+
+    // File header: AMD64, two sections, a PE32+ optional header, an executable DLL.
+    put(&mut file, 0x40, b"PE\0\0");
+    put(&mut file, 0x44, &u16(0x8664));
+    put(&mut file, 0x46, &u16(2));
+    put(&mut file, 0x54, &u16(0xF0));
+    put(&mut file, 0x56, &u16(0x2022));
+
+    // Optional header.
+    put(&mut file, 0x58, &u16(0x20B));
+    put(&mut file, 0x5A, &[14, 0]);
+    put(&mut file, 0x5C, &u32(0x200)); // size of code
+    put(&mut file, 0x60, &u32(0x200)); // size of initialized data
+    put(&mut file, 0x6C, &u32(0x1000)); // base of code
+    put(&mut file, 0x70, &u64(0x1_8000_0000)); // image base
+    put(&mut file, 0x78, &u32(0x1000)); // section alignment
+    put(&mut file, 0x7C, &u32(0x200)); // file alignment
+    put(&mut file, 0x80, &u16(6)); // operating system version
+    put(&mut file, 0x88, &u16(6)); // subsystem version
+    put(&mut file, 0x90, &u32(0x3000)); // size of image
+    put(&mut file, 0x94, &u32(0x200)); // size of headers
+    put(&mut file, 0x9C, &u16(2)); // subsystem
+    put(&mut file, 0x9E, &u16(0x160)); // DLL characteristics
+    put(&mut file, 0xA0, &u64(0x10_0000)); // stack reserve
+    put(&mut file, 0xA8, &u64(0x1000)); // stack commit
+    put(&mut file, 0xB0, &u64(0x10_0000)); // heap reserve
+    put(&mut file, 0xB8, &u64(0x1000)); // heap commit
+    put(&mut file, 0xC4, &u32(16)); // number of data directories
+
+    // The import directory and the import address table.
+    put(&mut file, 0xD0, &[u32(0x2028), u32(0x3C)].concat());
+    put(&mut file, 0x128, &[u32(0x2000), u32(0x28)].concat());
+
+    // Section headers: name, virtual size and address, raw size and offset, characteristics.
+    let sections = [
+        (b".text\0\0\0", [0x10, 0x1000, 0x200, 0x200], 0x6000_0020),
+        (
+            b".rdata\0\0",
+            [0xCB, 0x2000, 0x200, RDATA as u32],
+            0x4000_0040,
+        ),
+    ];
+    for (index, (name, layout, characteristics)) in sections.into_iter().enumerate() {
+        let header = 0x148 + index * 40;
+        put(&mut file, header, name);
+        put(&mut file, header + 8, &layout.map(u32).concat());
+        put(&mut file, header + 36, &u32(characteristics));
+    }
+
+    // ret
+    put(&mut file, 0x200, &[0xC3]);
+
+    // The import address tables, which name the functions the same way the lookup tables do.
+    let kernel32 = [u64(0x2090), u64(0x20A0), u64(0)].concat();
+    let user32 = [u64(1 << 63 | 17), u64(0)].concat();
+    put(&mut file, rdata(0x2000), &kernel32);
+    put(&mut file, rdata(0x2018), &user32);
+
+    // Import descriptors: lookup table, timestamp, forwarder chain, name, IAT. A zeroed one ends
+    // the directory.
+    for (index, (lookup, name, iat)) in [(0x2068, 0x20B0, 0x2000), (0x2080, 0x20C0, 0x2018)]
+        .into_iter()
+        .enumerate()
+    {
+        let descriptor = rdata(0x2028) + index * 20;
+        put(&mut file, descriptor, &u32(lookup));
+        put(&mut file, descriptor + 12, &[u32(name), u32(iat)].concat());
+    }
+
+    put(&mut file, rdata(0x2068), &kernel32);
+    put(&mut file, rdata(0x2080), &user32);
+
+    // Hints and names.
+    put(
+        &mut file,
+        rdata(0x2090),
+        &[&u16(0xCB)[..], b"CreateFileW\0"].concat(),
+    );
+    put(
+        &mut file,
+        rdata(0x20A0),
+        &[&u16(0x3C1)[..], b"LoadLibraryW\0"].concat(),
+    );
+    put(&mut file, rdata(0x20B0), b"KERNEL32.dll\0");
+    put(&mut file, rdata(0x20C0), b"USER32.dll\0");
+
+    file
+}
+
+fn import(module: &str, name: Option<&str>, ordinal: Option<u16>, iat: usize) -> Import {
+    Import {
+        module: module.to_owned(),
+        name: name.map(str::to_owned),
+        ordinal,
+        iat: Rva::new(iat),
+    }
+}
+
+#[test]
+fn imports_from_file() {
+    assert_eq!(
+        imports(&fixture()).unwrap(),
+        [
+            import("KERNEL32.dll", Some("CreateFileW"), None, 0x2000),
+            import("KERNEL32.dll", Some("LoadLibraryW"), None, 0x2008),
+            import("USER32.dll", None, Some(17), 0x2018),
+        ]
+    );
+}
+
+#[test]
+fn invalid_file() {
+    assert_eq!(imports(b"MZ"), Err(Error::InvalidHeader));
+    assert_eq!(imports(b"\x7fELF"), Err(Error::InvalidHeader));
+
+    // The headers are intact but the import directory is cut off.
+    assert_eq!(imports(&fixture()[..0x400]), Err(Error::InvalidHeader));
+}
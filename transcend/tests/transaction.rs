@@ -0,0 +1,128 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use libc::{memfd_create, mmap, MAP_FAILED, MAP_SHARED, PROT_EXEC, PROT_READ};
+use std::arch::global_asm;
+use std::fs::File;
+use std::io::Write;
+use std::mem::transmute;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use transcend::ptr::{Detour, HookError, Transaction};
+use transcend::Error;
+
+global_asm!(
+    // Spins in its first five bytes until the byte at `rdi` is set.
+    ".globl transcend_test_spin",
+    ".p2align 4",
+    "transcend_test_spin:",
+    "2:  cmp byte ptr [rdi], 0",
+    "    jne 2b",
+    "    mov eax, 1",
+    "    ret",
+    "",
+    ".globl transcend_test_sum",
+    ".p2align 4",
+    "transcend_test_sum:",
+    "    push rbp",
+    "    mov rbp, rsp",
+    "    lea eax, [rdi + rsi]",
+    "    pop rbp",
+    "    ret",
+);
+
+extern "C" {
+    fn transcend_test_spin(flag: *const AtomicBool) -> u32;
+    fn transcend_test_sum(a: u32, b: u32) -> u32;
+}
+
+type Spin = unsafe extern "C" fn(*const AtomicBool) -> u32;
+type Sum = unsafe extern "C" fn(u32, u32) -> u32;
+
+static SPINS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn spin(_: *const AtomicBool) -> u32 {
+    SPINS.fetch_add(1, Ordering::SeqCst);
+    2
+}
+
+unsafe extern "C" fn sum(a: u32, b: u32) -> u32 {
+    a + b + 100
+}
+
+#[test]
+fn commit() {
+    static SPINNING: AtomicBool = AtomicBool::new(true);
+
+    let worker = thread::spawn(|| unsafe { transcend_test_spin(&SPINNING) });
+    thread::sleep(Duration::from_millis(100));
+
+    let spin = unsafe { Detour::new_disabled(transcend_test_spin as Spin, spin as Spin) }.unwrap();
+    let sum = unsafe { Detour::new_disabled(transcend_test_sum as Sum, sum as Sum) }.unwrap();
+    assert_eq!(unsafe { transcend_test_sum(1, 2) }, 3);
+
+    unsafe { Transaction::begin().enable(&spin).enable(&sum).commit() }.unwrap();
+    assert!(spin.is_enabled() && sum.is_enabled());
+    assert_eq!(unsafe { transcend_test_sum(1, 2) }, 103);
+
+    // The worker was moved to the copy of the loop in the trampoline, it leaves through the rest of
+    // the original function without ever entering the detour.
+    SPINNING.store(false, Ordering::SeqCst);
+    assert_eq!(worker.join().unwrap(), 1);
+    assert_eq!(SPINS.load(Ordering::SeqCst), 0);
+
+    let flag = AtomicBool::new(false);
+    assert_eq!(unsafe { transcend_test_spin(&flag) }, 2);
+    assert_eq!(SPINS.load(Ordering::SeqCst), 1);
+
+    unsafe { Transaction::begin().disable(&spin).disable(&sum).commit() }.unwrap();
+    assert!(!spin.is_enabled() && !sum.is_enabled());
+    assert_eq!(unsafe { transcend_test_spin(&flag) }, 1);
+    assert_eq!(unsafe { transcend_test_sum(1, 2) }, 3);
+}
+
+#[test]
+fn rollback() {
+    // A function in a shared mapping of a file that is only open for reading, which can never be
+    // made writable.
+    let code: &[u8] = &[
+        0x55, // push rbp
+        0x48, 0x89, 0xE5, // mov rbp, rsp
+        0x8D, 0x04, 0x37, // lea eax, [rdi + rsi]
+        0x5D, // pop rbp
+        0xC3, // ret
+    ];
+
+    let fd = unsafe { memfd_create(c"transcend".as_ptr(), 0) };
+    assert!(fd >= 0);
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(code).unwrap();
+
+    let readonly = File::open(format!("/proc/self/fd/{fd}")).unwrap();
+    let page = unsafe {
+        mmap(
+            null_mut(),
+            code.len(),
+            PROT_READ | PROT_EXEC,
+            MAP_SHARED,
+            readonly.as_raw_fd(),
+            0,
+        )
+    };
+    assert_ne!(page, MAP_FAILED);
+
+    let target = unsafe { transmute::<*mut libc::c_void, Sum>(page) };
+    let readonly = unsafe { Detour::new_disabled(target, sum as Sum) }.unwrap();
+    let sum = unsafe { Detour::new_disabled(transcend_test_sum as Sum, sum as Sum) }.unwrap();
+
+    let error =
+        unsafe { Transaction::begin().enable(&sum).enable(&readonly).commit() }.unwrap_err();
+    assert_eq!(error, Error::Hook(HookError::Protect));
+
+    // The detour that could be written was restored with the one that could not.
+    assert!(!sum.is_enabled() && !readonly.is_enabled());
+    assert_eq!(unsafe { transcend_test_sum(1, 2) }, 3);
+    assert_eq!(unsafe { target(1, 2) }, 3);
+}
@@ -1,17 +1,217 @@
 use proc_macro::TokenStream;
-use quote::quote;
+use proc_macro2::Span;
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Error, Ident, Lit, Result, Token};
 
+/// Builds a `transcend::ptr::Pattern` from hex bytes.
+///
+/// `??` is a wildcard byte and `?` in place of a single digit is a wildcard nibble. A `&` in front of a
+/// byte marks the position scans should return instead of the start of the match:
+///
+/// ```
+/// use transcend::ptr::sig;
+///
+/// let pattern = sig![48 8B 0D &?? ?? ?? ?? 4? 8B ?5];
+/// assert_eq!(pattern.offset(), 3);
+///
+/// // Spaces between the bytes are optional, like in x64dbg.
+/// assert_eq!(sig![488B0D &???????? 4?8B?5], pattern);
+///
+/// // The pattern survives being passed through other macros.
+/// macro_rules! signature {
+///     ($($tokens:tt)*) => {
+///         sig![$($tokens)*]
+///     };
+/// }
+/// assert_eq!(signature![48 8B 0D &?? ?? ?? ?? 4? 8B ?5], pattern);
+/// ```
+///
+/// The macro only sees Rust tokens, so bytes like `1E` or `0E` that start like a float literal with an
+/// exponent are rejected by the compiler before it runs. Glue them to a neighbouring byte, as in
+/// `8B1E` or `1E05`, or parse the signature at runtime instead.
+///
+/// Every byte must be two hex digits or wildcards:
+///
+/// ```compile_fail
+/// transcend::ptr::sig![48 8G];
+/// ```
+///
+/// ```compile_fail
+/// transcend::ptr::sig![48 8];
+/// ```
+///
+/// The signature must not be empty or start with a wildcard byte:
+///
+/// ```compile_fail
+/// transcend::ptr::sig![];
+/// ```
+///
+/// ```compile_fail
+/// transcend::ptr::sig![?? 8B 05];
+/// ```
+///
+/// Only one byte can be marked, and the mark must be followed by one:
+///
+/// ```compile_fail
+/// transcend::ptr::sig![48 &8B &05];
+/// ```
+///
+/// ```compile_fail
+/// transcend::ptr::sig![48 8B &];
+/// ```
 #[proc_macro]
 pub fn sig(input: TokenStream) -> TokenStream {
-    let input = input.to_string().replace("??", "FF");
-
-    let bytes: Vec<u8> = input
-        .split_ascii_whitespace()
-        .map(|hex| u8::from_str_radix(hex, 16).unwrap())
-        .collect();
+    let Signature {
+        bytes,
+        mask,
+        offset,
+    } = parse_macro_input!(input as Signature);
 
     quote! {
-        &[#(#bytes),*]
+        ::transcend::ptr::Pattern::from_static(&[#(#bytes),*], &[#(#mask),*], #offset)
     }
     .into()
 }
+
+struct Signature {
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+    offset: usize,
+}
+
+impl Parse for Signature {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut bytes = Vec::new();
+        let mut mask = Vec::new();
+        let mut capture: Option<(usize, Span)> = None;
+        // The first nibble of a byte whose second one has not been seen yet.
+        let mut pending: Option<(char, Span)> = None;
+
+        for piece in pieces(input)? {
+            let (digits, span) = match piece {
+                Piece::Capture(span) => {
+                    if pending.is_some() {
+                        return Err(Error::new(span, "`&` must mark a whole byte"));
+                    }
+
+                    if capture.is_some() {
+                        return Err(Error::new(span, "only one byte can be marked with `&`"));
+                    }
+
+                    capture = Some((bytes.len(), span));
+                    continue;
+                }
+                Piece::Wildcard(span) => ("?".to_owned(), span),
+                Piece::Digits(digits, span) => {
+                    // Digits only complete a byte started by a wildcard nibble as in `?5`, so that
+                    // `48 ? 8B` is not silently read as `48 ?8 B`.
+                    if let Some((high, _)) = pending.filter(|_| digits.len() != 1) {
+                        return Err(invalid(&format!("{high}{digits}"), span));
+                    }
+
+                    (digits, span)
+                }
+            };
+
+            for digit in digits.chars() {
+                let Some((high, start)) = pending.take() else {
+                    pending = Some((digit, span));
+                    continue;
+                };
+
+                let text = format!("{high}{digit}");
+                let (value, nibbles) =
+                    parse_byte(high, digit).ok_or_else(|| invalid(&text, start))?;
+
+                if bytes.is_empty() && nibbles == 0x00 {
+                    return Err(Error::new(
+                        start,
+                        "signature must not start with a wildcard",
+                    ));
+                }
+
+                bytes.push(value);
+                mask.push(nibbles);
+            }
+        }
+
+        if let Some((high, span)) = pending {
+            return Err(invalid(&high.to_string(), span));
+        }
+
+        if bytes.is_empty() {
+            return Err(Error::new(Span::call_site(), "signature must not be empty"));
+        }
+
+        let offset = match capture {
+            Some((offset, span)) if offset == bytes.len() => {
+                return Err(Error::new(span, "`&` must be followed by a byte"));
+            }
+            Some((offset, _)) => offset,
+            None => 0,
+        };
+
+        Ok(Self {
+            bytes,
+            mask,
+            offset,
+        })
+    }
+}
+
+fn invalid(text: &str, span: Span) -> Error {
+    Error::new(
+        span,
+        format!("invalid byte `{text}`, expected two hex digits or wildcards"),
+    )
+}
+
+/// A single token of a signature.
+///
+/// The tokenizer splits `4?`, `?5` and `??` in two and does not tell whether there was whitespace in
+/// between, so bytes are put back together from the digits alone.
+enum Piece {
+    /// The text of a literal or identifier, such as `48`, `8B`, `E8` or the `4` of `4?`.
+    Digits(String, Span),
+    /// A `?` standing for a single nibble.
+    Wildcard(Span),
+    /// A `&` marking the next byte.
+    Capture(Span),
+}
+
+fn pieces(input: ParseStream) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+
+    while !input.is_empty() {
+        let piece = if input.peek(Token![?]) {
+            Piece::Wildcard(input.parse::<Token![?]>()?.span)
+        } else if input.peek(Token![&]) {
+            Piece::Capture(input.parse::<Token![&]>()?.span)
+        } else if input.peek(Lit) {
+            let lit: Lit = input.parse()?;
+            Piece::Digits(lit.to_token_stream().to_string(), lit.span())
+        } else if input.peek(Ident) {
+            let ident: Ident = input.parse()?;
+            Piece::Digits(ident.to_string(), ident.span())
+        } else {
+            return Err(input.error("expected a hex byte like `8B` or a wildcard like `??`"));
+        };
+
+        pieces.push(piece);
+    }
+
+    Ok(pieces)
+}
+
+/// Parses a single byte like `8B`, `4?`, `?5` or `??` into its value and mask.
+fn parse_byte(high: char, low: char) -> Option<(u8, u8)> {
+    let nibble = |c: char| match c {
+        '?' => Some((0x0, 0x0)),
+        c => c.to_digit(16).map(|digit| (digit as u8, 0xF)),
+    };
+
+    let (high, high_mask) = nibble(high)?;
+    let (low, low_mask) = nibble(low)?;
+    Some((high << 4 | low, high_mask << 4 | low_mask))
+}